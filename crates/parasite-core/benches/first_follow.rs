@@ -0,0 +1,154 @@
+//! FIRST_k, FOLLOW_k and the LL(k) table against the [`baseline`] on uninterned terminals,
+//! run with `cargo bench --features bench`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use parasite_core::{
+    baseline,
+    builder::Syntactical,
+    combinators::{DelimitedBy, Identifier, Just, NewLine, SeparatedBy},
+    grammar::{Grammar, Id, Key, Rule, Symbol},
+    test_util::XorShift,
+};
+use rand_core::RngCore;
+
+type Args = DelimitedBy<Just<'('>, Just<')'>, SeparatedBy<Just<','>, Identifier>>;
+type Line = (
+    Option<Just<'#'>>,
+    Identifier,
+    Option<Args>,
+    (Vec<(bool, u8)>, NewLine),
+);
+type Program = Vec<(Line, Option<Vec<u16>>)>;
+
+fn program() -> Grammar {
+    let mut grammar = Grammar::new(Key::of::<Program>());
+    let mut stack = Vec::new();
+
+    Program::generate(&mut grammar, &mut stack);
+
+    grammar
+}
+
+/// A grammar over 300 nonterminals and 40 terminals in the shape of a programming language:
+/// nonterminals mostly invoke later ones, and earlier ones only after their first symbol
+fn generated() -> Grammar {
+    const NONTERMINALS: usize = 300;
+    const TERMINALS: usize = 40;
+
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut next = |bound: usize| (rng.next_u64() % bound as u64) as usize;
+
+    let nonterminals = (0..NONTERMINALS)
+        .map(|n| Key::new(format!("N{n}")))
+        .collect::<Vec<_>>();
+    let terminals = (0..TERMINALS)
+        .map(|t| Key::new(format!("t{t}")))
+        .collect::<Vec<_>>();
+
+    let mut grammar = Grammar::new(nonterminals[0].clone());
+    for (n, key) in nonterminals.iter().enumerate() {
+        let mut rule = Rule::new();
+        for id in 0..1 + next(4) {
+            let mut symbols = Vec::new();
+            for position in 0..next(5) {
+                let later = n + 1 + next(8);
+                let symbol = match next(6) {
+                    0..=2 => Symbol::terminal(terminals[next(TERMINALS)].clone()),
+                    3 if position > 0 => Symbol::nonterminal(nonterminals[next(n + 1)].clone()),
+                    _ if later < NONTERMINALS => Symbol::nonterminal(nonterminals[later].clone()),
+                    _ => Symbol::terminal(terminals[next(TERMINALS)].clone()),
+                };
+                symbols.push(symbol);
+            }
+            if symbols.is_empty() {
+                symbols.push(Symbol::Epsilon);
+            }
+            rule.insert(Id(id), symbols);
+        }
+        grammar.insert(key.clone(), rule);
+    }
+
+    grammar
+}
+
+fn grammars() -> [(&'static str, Grammar); 2] {
+    [("program", program()), ("generated", generated())]
+}
+
+/// Few samples for k = 3, where the generated grammar takes up to seconds
+fn sample_size(k: usize) -> usize {
+    match k {
+        3 => 10,
+        _ => 100,
+    }
+}
+
+/// Whether to run the baseline as well: on the generated grammar with k = 3 its FIRST_k
+/// alone took 35s against 1.5s, too long to sample
+fn with_baseline(name: &str, k: usize) -> bool {
+    name != "generated" || k < 3
+}
+
+fn first_k(c: &mut Criterion) {
+    let mut group = c.benchmark_group("first_k");
+    for (name, grammar) in grammars() {
+        for k in 1..=3 {
+            group.sample_size(sample_size(k));
+            group.bench_with_input(BenchmarkId::new(name, k), &k, |b, &k| {
+                b.iter(|| grammar.first_k(k))
+            });
+            if with_baseline(name, k) {
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{name}-baseline"), k),
+                    &k,
+                    |b, &k| b.iter(|| baseline::first_k(&grammar, k)),
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+fn follow_k(c: &mut Criterion) {
+    let mut group = c.benchmark_group("follow_k");
+    for (name, grammar) in grammars() {
+        for k in 1..=3 {
+            group.sample_size(sample_size(k));
+            let first = grammar.first_k(k);
+            group.bench_with_input(BenchmarkId::new(name, k), &k, |b, &k| {
+                b.iter(|| grammar.follow_k(k, &first))
+            });
+            if with_baseline(name, k) {
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{name}-baseline"), k),
+                    &k,
+                    |b, &k| b.iter(|| baseline::follow_k(&grammar, k, &first)),
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+fn table(c: &mut Criterion) {
+    let mut group = c.benchmark_group("table");
+    for (name, grammar) in grammars() {
+        for k in 1..=3 {
+            group.sample_size(sample_size(k));
+            group.bench_with_input(BenchmarkId::new(name, k), &k, |b, &k| {
+                b.iter(|| grammar.table(k))
+            });
+            if with_baseline(name, k) {
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{name}-baseline"), k),
+                    &k,
+                    |b, &k| b.iter(|| baseline::table(&grammar, k)),
+                );
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, first_k, follow_k, table);
+criterion_main!(benches);
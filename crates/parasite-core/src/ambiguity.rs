@@ -0,0 +1,243 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    grammar::{Grammar, Id, Key, Terminal},
+    intern::{Interned, Sym, SymbolId},
+};
+
+/// A terminal string with two different leftmost derivations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguity {
+    pub terminals: Vec<Terminal>,
+    pub derivations: [Vec<(Key, Id)>; 2],
+}
+
+impl Grammar {
+    /// Searches the strings of at most `max_len` terminals for one with two leftmost derivations.
+    /// Sentential forms with more than `max_len` symbols plus one per nonterminal are not explored,
+    /// so `None` is no proof that the grammar is unambiguous
+    pub fn find_ambiguity(&self, max_len: usize) -> Option<Ambiguity> {
+        let interned = self.interned();
+        let shortest = Shortest::new(interned);
+        let max_form = max_len + interned.nonterminals.len();
+
+        // the length of the shortest terminal string a form derives
+        let min_len = |form: &[Sym]| -> Option<usize> {
+            form.iter()
+                .map(|symbol| match symbol {
+                    Sym::Terminal(_) => Some(1),
+                    Sym::Nonterminal(nonterminal) => shortest.len[nonterminal.index()],
+                })
+                .sum()
+        };
+
+        let start = vec![Sym::Nonterminal(interned.nonterminal_id(&self.start))];
+        if min_len(&start).is_none_or(|len| len > max_len) {
+            return None;
+        }
+
+        // the first leftmost derivation which reached each form
+        let mut reached = HashMap::from([(start.clone(), Vec::new())]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(form) = queue.pop_front() {
+            let Some(position) = form
+                .iter()
+                .position(|symbol| matches!(symbol, Sym::Nonterminal(_)))
+            else {
+                continue;
+            };
+            let Sym::Nonterminal(rule) = form[position] else {
+                unreachable!()
+            };
+
+            for (index, (_, symbols)) in interned.rules[rule.index()].iter().enumerate() {
+                let mut derived = form[..position].to_vec();
+                derived.extend_from_slice(symbols);
+                derived.extend_from_slice(&form[position + 1..]);
+
+                if derived.len() > max_form || min_len(&derived).is_none_or(|len| len > max_len) {
+                    continue;
+                }
+
+                let mut applied = reached[&form].clone();
+                applied.push((rule.index(), index));
+
+                match reached.get(&derived) {
+                    // two leftmost derivations of the same form, any completion is ambiguous
+                    Some(other) if *other != applied => {
+                        let other = other.clone();
+                        let (completion, terminals) = shortest.complete(interned, &derived);
+
+                        let resolve = |mut applied: Vec<(usize, usize)>| {
+                            applied.extend_from_slice(&completion);
+                            applied
+                                .into_iter()
+                                .map(|(rule, index)| {
+                                    let key = interned.nonterminals[rule].clone();
+                                    (key, interned.rules[rule][index].0)
+                                })
+                                .collect()
+                        };
+
+                        return Some(Ambiguity {
+                            terminals: interned.resolve(&terminals),
+                            derivations: [resolve(other), resolve(applied)],
+                        });
+                    }
+                    Some(_) => (),
+                    None => {
+                        reached.insert(derived.clone(), applied);
+                        queue.push_back(derived);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// The shortest terminal string of each nonterminal and the production it starts with
+pub(crate) struct Shortest {
+    pub(crate) len: Vec<Option<usize>>,
+    production: Vec<usize>,
+}
+
+impl Shortest {
+    pub(crate) fn new(interned: &Interned) -> Self {
+        let mut len = vec![None; interned.rules.len()];
+        let mut production = vec![0; interned.rules.len()];
+
+        // like Dijkstra, a production may only be used once all of its nonterminals are settled,
+        // so that following the productions always terminates
+        loop {
+            let mut best = None;
+            for (rule, productions) in interned.rules.iter().enumerate() {
+                if len[rule].is_some() {
+                    continue;
+                }
+
+                for (index, (_, symbols)) in productions.iter().enumerate() {
+                    let cost = symbols
+                        .iter()
+                        .map(|symbol| match symbol {
+                            Sym::Terminal(_) => Some(1),
+                            Sym::Nonterminal(nonterminal) => len[nonterminal.index()],
+                        })
+                        .sum::<Option<usize>>();
+
+                    if let Some(cost) = cost {
+                        if best.is_none_or(|(best, _, _)| cost < best) {
+                            best = Some((cost, rule, index));
+                        }
+                    }
+                }
+            }
+
+            let Some((cost, rule, index)) = best else {
+                break;
+            };
+            len[rule] = Some(cost);
+            production[rule] = index;
+        }
+
+        Self { len, production }
+    }
+
+    /// The production of `nonterminal` which starts its shortest terminal string
+    pub(crate) fn production(&self, nonterminal: SymbolId) -> Option<usize> {
+        self.len[nonterminal.index()].map(|_| self.production[nonterminal.index()])
+    }
+
+    /// Derives the shortest terminal string of `form` leftmost,
+    /// returns the applied productions and the terminals
+    pub(crate) fn complete(
+        &self,
+        interned: &Interned,
+        form: &[Sym],
+    ) -> (Vec<(usize, usize)>, Vec<SymbolId>) {
+        let mut applied = Vec::new();
+        let mut terminals = Vec::new();
+        let mut pending = form.iter().rev().copied().collect::<Vec<_>>();
+
+        while let Some(symbol) = pending.pop() {
+            match symbol {
+                Sym::Terminal(terminal) => terminals.push(terminal),
+                Sym::Nonterminal(nonterminal) => {
+                    let index = self.production[nonterminal.index()];
+                    applied.push((nonterminal.index(), index));
+                    pending.extend(interned.rules[nonterminal.index()][index].1.iter().rev());
+                }
+            }
+        }
+
+        (applied, terminals)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Id, Key, Rule, Symbol, Terminal},
+        span::Token,
+    };
+
+    /// E -> E plus E | n
+    fn expression() -> Grammar {
+        let e = Key::new("E");
+        let mut grammar = Grammar::new(e.clone());
+
+        let mut rule = Rule::new();
+        rule.insert(
+            Id(0),
+            vec![
+                Symbol::nonterminal(e.clone()),
+                Symbol::terminal(Key::new("plus")),
+                Symbol::nonterminal(e.clone()),
+            ],
+        );
+        rule.insert(Id(1), vec![Symbol::terminal(Key::new("n"))]);
+        grammar.insert(e, rule);
+
+        grammar
+    }
+
+    #[test]
+    fn ambiguity_1() {
+        let grammar = expression();
+        assert_eq!(grammar.find_ambiguity(3), None);
+
+        let ambiguity = grammar.find_ambiguity(5).unwrap();
+        let [first, second] = &ambiguity.derivations;
+        assert_ne!(first, second);
+        assert_eq!(
+            ambiguity.terminals,
+            ["n", "plus", "n", "plus", "n"].map(|name| Terminal::from(Key::new(name)))
+        );
+
+        let tokens = Token::indexed(&ambiguity.terminals).collect::<Vec<_>>();
+        for derivation in &ambiguity.derivations {
+            assert_eq!(grammar.tree(derivation, &tokens).derivation(), *derivation);
+        }
+    }
+
+    #[test]
+    fn ambiguity_2() {
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+        assert_eq!(grammar.find_ambiguity(6), None);
+
+        // Option<Option<bool>> derives the empty string as None and as Some(None)
+        let mut grammar = Grammar::new(Key::of::<Option<Option<bool>>>());
+        let mut stack = Vec::new();
+
+        Option::<Option<bool>>::generate(&mut grammar, &mut stack);
+        let ambiguity = grammar.find_ambiguity(0).unwrap();
+        assert!(ambiguity.terminals.is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! FIRST_k, FOLLOW_k and the LL(k) table computed directly on [`Terminals`], the way they
+//! were before the analyses interned the grammar. Only the benchmarks compare against it
+
+use std::collections::HashSet;
+
+use crate::{
+    first::{FirstSet, FirstSets, FirstTable},
+    follow::{FollowSet, FollowSets},
+    grammar::{Grammar, Symbol, Terminal, Terminals},
+    table::{Row, Table},
+};
+
+/// The words of `left` followed by the words of `right`, cut after `k` terminals
+fn concat_k(left: &HashSet<Terminals>, right: &HashSet<Terminals>, k: usize) -> FirstSet {
+    let mut words = FirstSet::new();
+    if right.is_empty() {
+        return words;
+    }
+
+    for prefix in left {
+        if prefix.len() >= k {
+            words.insert(prefix.clone());
+            continue;
+        }
+        for suffix in right {
+            let mut word = prefix.clone();
+            word.extend(suffix.iter().take(k - prefix.len()).cloned());
+            words.insert(word);
+        }
+    }
+    words
+}
+
+/// FIRST_k of `symbols` with the FIRST_k sets of the nonterminals known so far
+fn first_k_of(symbols: &[Symbol], table: &FirstTable, k: usize) -> FirstSet {
+    let mut words = FirstSet::from([Vec::new()]);
+    for symbol in symbols {
+        let next = match symbol {
+            Symbol::Epsilon => continue,
+            Symbol::Terminal(terminal) => FirstSet::from([vec![terminal.clone()]]),
+            Symbol::Nonterminal(nonterminal) => {
+                table[&nonterminal.0].values().flatten().cloned().collect()
+            }
+        };
+        words = concat_k(&words, &next, k);
+    }
+    words
+}
+
+/// Recomputes the FIRST_k sets of all productions until none of them changes
+pub fn first_k(grammar: &Grammar, k: usize) -> FirstTable {
+    let mut table = FirstTable::new();
+    for (key, rule) in &grammar.productions {
+        let sets = rule.keys().map(|id| (*id, FirstSet::new())).collect();
+        table.insert(key.clone(), sets);
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (key, rule) in &grammar.productions {
+            let mut sets = FirstSets::new();
+            for (id, symbols) in rule {
+                sets.insert(*id, first_k_of(symbols, &table, k));
+            }
+            if table[key] != sets {
+                table.insert(key.clone(), sets);
+                changed = true;
+            }
+        }
+    }
+
+    table
+}
+
+/// Adds FIRST_k of the rest of a production followed by FOLLOW_k of its nonterminal
+/// to each invoked nonterminal until none of the sets changes
+pub fn follow_k(grammar: &Grammar, k: usize, first_table: &FirstTable) -> FollowSets {
+    let mut sets = grammar
+        .keys()
+        .map(|key| (key, FollowSet::new()))
+        .collect::<FollowSets>();
+    sets.get_mut(&grammar.start)
+        .unwrap()
+        .insert(vec![Terminal::eoi()]);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for (key, rule) in &grammar.productions {
+            for symbols in rule.values() {
+                for (position, symbol) in symbols.iter().enumerate() {
+                    let Symbol::Nonterminal(invoked) = symbol else {
+                        continue;
+                    };
+
+                    let rest = first_k_of(&symbols[position + 1..], first_table, k);
+                    for word in concat_k(&rest, &sets[key], k) {
+                        changed |= sets.get_mut(&invoked.0).unwrap().insert(word);
+                    }
+                }
+            }
+        }
+    }
+
+    sets
+}
+
+/// The LL(k) table of the start symbol, of competing productions the last one wins
+pub fn table(grammar: &Grammar, k: usize) -> Table {
+    let first_table = first_k(grammar, k);
+    let follow_sets = follow_k(grammar, k, &first_table);
+
+    let mut table = Table::new();
+    for key in grammar.keys() {
+        let mut ids = first_table[&key].keys().copied().collect::<Vec<_>>();
+        ids.sort();
+
+        let mut row = Row::new();
+        for id in ids {
+            for word in concat_k(&first_table[&key][&id], &follow_sets[&key], k) {
+                row.insert(word, id);
+            }
+        }
+        table.insert(key, row);
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::random_grammar;
+
+    #[test]
+    fn baseline() {
+        for seed in 1..=100 {
+            let grammar = random_grammar(seed);
+            for k in 1..=3 {
+                let first_table = grammar.first_k(k);
+                assert_eq!(super::first_k(&grammar, k), first_table, "seed {seed}");
+
+                let follow_sets = grammar.follow_k(k, &first_table);
+                assert_eq!(
+                    super::follow_k(&grammar, k, &first_table),
+                    follow_sets,
+                    "seed {seed}"
+                );
+
+                let table = grammar.table(k);
+                assert_eq!(super::table(&grammar, k), table, "seed {seed}");
+            }
+        }
+    }
+}
@@ -1,16 +1,47 @@
 use crate::grammar::{Grammar, Id, Key, Rule, Symbol};
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, LinkedList, VecDeque};
-pub trait Syntactical {
-    fn generate(_grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
-        Symbol::terminal(Key::of::<Self>())
+/// A type which describes its own syntax.
+///
+/// Symbols are identified by the [`TypeId`](std::any::TypeId) of their types,
+/// which only exists for `'static` types, so syntax trees own their data.
+pub trait Syntactical: 'static {
+    fn generate(grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
+        grammar.terminal::<Self>()
+    }
+
+    /// The key of the type, which can be given another name for display
+    fn key() -> Key {
+        Key::of::<Self>()
+    }
+
+    /// How the type is called in error messages, if it is a terminal
+    fn display_name() -> String {
+        format!("`{}`", Self::key())
     }
 
     fn visited(grammar: &Grammar, stack: &Vec<Key>) -> bool {
-        let key = Key::of::<Self>();
+        let key = Self::key();
         grammar.contains(&key) || stack.contains(&key)
     }
 }
 
+impl<T: Syntactical + 'static> Syntactical for Box<T> {
+    fn generate(grammar: &mut Grammar, stack: &mut Vec<Key>) -> Symbol {
+        let key = Key::of::<Self>();
+
+        if !Self::visited(grammar, stack) {
+            stack.push(key.clone());
+
+            let mut rule = Rule::new();
+            rule.insert(Id(0), vec![T::generate(grammar, stack)]);
+
+            grammar.insert(key.clone(), rule);
+        }
+
+        Symbol::nonterminal(key)
+    }
+}
+
 impl<T: Syntactical + 'static> Syntactical for Option<T> {
     fn generate(grammar: &mut Grammar, stack: &mut Vec<Key>) -> Symbol {
         let key = Key::of::<Self>();
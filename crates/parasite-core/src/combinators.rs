@@ -2,7 +2,8 @@ use std::marker::PhantomData;
 
 use crate::{
     builder::Syntactical,
-    grammar::{Grammar, Id, Key, Rule, Symbol},
+    grammar::{Grammar, Id, Key, Production, Rule, Symbol},
+    span::Span,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -51,12 +52,16 @@ impl<T: Syntactical + 'static> Syntactical for NonEmptyVec<T> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Just<const CHAR: char>();
 
 impl<const CHAR: char> Syntactical for Just<CHAR> {
-    fn generate(_grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
-        Symbol::terminal(Key::of::<Self>())
+    fn generate(grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
+        grammar.terminal::<Self>()
+    }
+
+    fn display_name() -> String {
+        format!("`{CHAR:?}`")
     }
 }
 
@@ -151,6 +156,10 @@ impl<T: Syntactical + 'static, L: Syntactical + 'static, R: Syntactical + 'stati
                 ],
             );
 
+            let production = Production::new("DelimitedBy")
+                .with_fields([Some("left"), Some("inner"), Some("right")])
+                .with_origin(DELIMITED_BY);
+            grammar.set_production(key.clone(), Id(0), production);
             grammar.insert(key.clone(), rule);
         }
 
@@ -158,7 +167,10 @@ impl<T: Syntactical + 'static, L: Syntactical + 'static, R: Syntactical + 'stati
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Origin of the productions of [`DelimitedBy`]
+pub(crate) const DELIMITED_BY: &str = concat!(module_path!(), "::DelimitedBy");
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct End;
 
 impl Syntactical for End {
@@ -171,26 +183,38 @@ impl Syntactical for End {
 pub struct Any(pub char);
 
 impl Syntactical for Any {
-    fn generate(_grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
-        Symbol::terminal(Key::of::<Self>())
+    fn generate(grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
+        grammar.terminal::<Self>()
+    }
+
+    fn display_name() -> String {
+        "any character".to_string()
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct NewLine;
 
 impl Syntactical for NewLine {
-    fn generate(_grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
-        Symbol::terminal(Key::of::<Self>())
+    fn generate(grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
+        grammar.terminal::<Self>()
+    }
+
+    fn display_name() -> String {
+        "newline".to_string()
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct WhiteSpace;
 
 impl Syntactical for WhiteSpace {
-    fn generate(_grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
-        Symbol::terminal(Key::of::<Self>())
+    fn generate(grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
+        grammar.terminal::<Self>()
+    }
+
+    fn display_name() -> String {
+        "whitespace".to_string()
     }
 }
 
@@ -198,7 +222,31 @@ impl Syntactical for WhiteSpace {
 pub struct Identifier(pub String);
 
 impl Syntactical for Identifier {
-    fn generate(_grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
-        Symbol::terminal(Key::of::<Self>())
+    fn generate(grammar: &mut Grammar, _stack: &mut Vec<Key>) -> Symbol {
+        grammar.terminal::<Self>()
+    }
+
+    fn display_name() -> String {
+        "identifier".to_string()
+    }
+}
+
+/// Captures the span of `T` in the source, without changing the grammar of `T`.
+/// The span is set from the tokens of a parse with [`Unparse::respan`](crate::unparse::Unparse::respan)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Span) -> Self {
+        Self { value, span }
+    }
+}
+
+impl<T: Syntactical + 'static> Syntactical for Spanned<T> {
+    fn generate(grammar: &mut Grammar, stack: &mut Vec<Key>) -> Symbol {
+        T::generate(grammar, stack)
     }
 }
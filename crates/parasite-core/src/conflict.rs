@@ -0,0 +1,358 @@
+use core::fmt;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashSet},
+};
+
+use owo_colors::OwoColorize;
+
+use crate::{
+    ambiguity::Shortest,
+    first::concat_k,
+    grammar::{Grammar, Id, Key, Symbol, Terminal, Terminals},
+    intern::{Interned, Sym, SymbolId, Word, WordSet},
+};
+
+/// How many nonterminals with a right context the search for examples reaches at most,
+/// as the right contexts may multiply with every invocation
+const SEARCH_LIMIT: usize = 1 << 12;
+
+/// Several productions of `key` in one cell of the LL(k) table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: Key,
+    pub lookahead: Terminals,
+    /// The competing productions, in the order of their ids
+    pub ids: Vec<Id>,
+    /// One example per competing production which the search found one for
+    pub examples: Vec<Example>,
+}
+
+/// A sentential form `prefix key suffix` derived from the entry, in which the production
+/// `id` of `key` derives a string starting with the lookahead of the conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    pub id: Id,
+    /// The shortest input before `key`
+    pub prefix: Vec<Terminal>,
+    pub suffix: Vec<Symbol>,
+}
+
+impl Grammar {
+    /// The conflicts of the LL(k) table of the start symbol
+    pub fn conflicts(&self, k: usize) -> Vec<Conflict> {
+        self.conflicts_from(k, &self.start)
+    }
+
+    pub fn conflicts_from(&self, k: usize, entry: &Key) -> Vec<Conflict> {
+        self.conflicts_within(k, entry, SEARCH_LIMIT)
+    }
+
+    /// The conflicts with examples from the first `limit` nonterminals the search reaches
+    pub(crate) fn conflicts_within(&self, k: usize, entry: &Key, limit: usize) -> Vec<Conflict> {
+        let interned = self.interned();
+        let search = Search::new(interned, k, entry, limit);
+
+        let mut conflicts = Vec::new();
+        for (of, cells) in interned.cells(k, entry).into_iter().enumerate() {
+            let mut cells = cells
+                .into_iter()
+                .filter(|(_, ids)| ids.len() > 1)
+                .collect::<Vec<_>>();
+            cells.sort();
+
+            for (lookahead, ids) in cells {
+                let examples = ids
+                    .iter()
+                    .filter_map(|id| search.example(SymbolId(of as u32), *id, &lookahead))
+                    .collect();
+
+                conflicts.push(Conflict {
+                    key: interned.nonterminals[of].clone(),
+                    lookahead: interned.resolve(&lookahead),
+                    ids,
+                    examples,
+                });
+            }
+        }
+
+        conflicts
+    }
+}
+
+/// A nonterminal after the shortest input which reaches it with some right context
+#[derive(Debug, Clone)]
+struct Reached {
+    nonterminal: SymbolId,
+    /// FIRST_k of the right context followed by the end of the input
+    context: WordSet,
+    prefix: Vec<SymbolId>,
+    suffix: Vec<Sym>,
+}
+
+/// Finds the shortest inputs which reach the nonterminals with each right context
+struct Search<'a> {
+    interned: &'a Interned,
+    k: usize,
+    first: Vec<Vec<(Id, WordSet)>>,
+    /// Dijkstra's order of the reached nonterminals, so ordered by the length of the prefix
+    reached: Vec<Reached>,
+}
+
+impl<'a> Search<'a> {
+    fn new(interned: &'a Interned, k: usize, entry: &Key, limit: usize) -> Self {
+        let first = interned.first_k(k);
+        let first_sets = first
+            .iter()
+            .map(|rule| rule.iter().flat_map(|(_, words)| words).cloned().collect())
+            .collect::<Vec<WordSet>>();
+        let shortest = Shortest::new(interned);
+
+        let start = Reached {
+            nonterminal: interned.nonterminal_id(entry),
+            context: WordSet::from_iter([Word::from_slice(&[interned.eoi()])]),
+            prefix: Vec::new(),
+            suffix: Vec::new(),
+        };
+
+        let mut pending = vec![start];
+        let mut queue = BinaryHeap::from([Reverse((0, 0))]);
+        let mut settled = HashSet::new();
+        let mut reached = Vec::new();
+
+        while let Some(Reverse((_, index))) = queue.pop() {
+            if reached.len() == limit {
+                break;
+            }
+
+            let current = pending[index].clone();
+            let context = current.context.iter().cloned().collect::<BTreeSet<_>>();
+            if !settled.insert((current.nonterminal, context)) {
+                continue;
+            }
+
+            for (_, symbols) in &interned.rules[current.nonterminal.index()] {
+                for (position, symbol) in symbols.iter().enumerate() {
+                    let Sym::Nonterminal(invoked) = symbol else {
+                        continue;
+                    };
+                    // the input before the symbol has to be derivable
+                    if symbols[..position].iter().any(|symbol| match symbol {
+                        Sym::Terminal(_) => false,
+                        Sym::Nonterminal(nonterminal) => {
+                            shortest.len[nonterminal.index()].is_none()
+                        }
+                    }) {
+                        continue;
+                    }
+
+                    let rest = &symbols[position + 1..];
+                    let mut prefix = current.prefix.clone();
+                    prefix.extend(shortest.complete(interned, &symbols[..position]).1);
+                    let mut suffix = rest.to_vec();
+                    suffix.extend_from_slice(&current.suffix);
+
+                    let context = concat_k(
+                        &interned.first_k_of(k, rest, &first_sets),
+                        &current.context,
+                        k,
+                    );
+
+                    queue.push(Reverse((prefix.len(), pending.len())));
+                    pending.push(Reached {
+                        nonterminal: *invoked,
+                        context,
+                        prefix,
+                        suffix,
+                    });
+                }
+            }
+
+            reached.push(current);
+        }
+
+        Self {
+            interned,
+            k,
+            first,
+            reached,
+        }
+    }
+
+    /// The first context in which production `id` of `nonterminal` may start with `lookahead`
+    fn example(&self, nonterminal: SymbolId, id: Id, lookahead: &Word) -> Option<Example> {
+        let (_, first) = self.first[nonterminal.index()]
+            .iter()
+            .find(|(other, _)| *other == id)?;
+
+        let reached = self.reached.iter().find(|reached| {
+            reached.nonterminal == nonterminal
+                && concat_k(first, &reached.context, self.k)
+                    .iter()
+                    .any(|word| word.starts_with(lookahead))
+        })?;
+
+        Some(Example {
+            id,
+            prefix: self.interned.resolve(&reached.prefix),
+            suffix: reached
+                .suffix
+                .iter()
+                .map(|symbol| self.interned.symbol(*symbol))
+                .collect(),
+        })
+    }
+}
+
+impl Conflict {
+    /// Displays the conflict with the names of `grammar`
+    pub fn display<'a>(&'a self, grammar: &'a Grammar) -> ConflictDisplay<'a> {
+        ConflictDisplay {
+            conflict: self,
+            grammar,
+        }
+    }
+}
+
+pub struct ConflictDisplay<'a> {
+    conflict: &'a Conflict,
+    grammar: &'a Grammar,
+}
+
+impl fmt::Display for ConflictDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Conflict {
+            key,
+            lookahead,
+            ids,
+            examples,
+        } = self.conflict;
+        let grammar = self.grammar;
+
+        let names = ids
+            .iter()
+            .map(|id| format!("`{}`", grammar.production_name(key, *id)))
+            .collect::<Vec<_>>();
+        let lookahead = lookahead
+            .iter()
+            .map(|terminal| grammar.display_name(terminal))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let all = match names.len() {
+            2 => "both",
+            _ => "all of",
+        };
+        writeln!(
+            f,
+            "{} {} accept {lookahead} here",
+            all.bold(),
+            names.join(" and ")
+        )?;
+
+        for (name, id) in names.iter().zip(ids) {
+            write!(f, "\t{name}:")?;
+            let Some(example) = examples.iter().find(|example| example.id == *id) else {
+                writeln!(f, " {}", "no example found".dimmed())?;
+                continue;
+            };
+            for terminal in &example.prefix {
+                write!(f, " {}", grammar.display_name(terminal))?;
+            }
+            write!(f, " {} {}", "•".dimmed(), key.italic())?;
+            for symbol in &example.suffix {
+                match symbol {
+                    Symbol::Terminal(terminal) => write!(f, " {}", grammar.display_name(terminal))?,
+                    symbol => write!(f, " {symbol}")?,
+                }
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        grammar::{Id, Key, Symbol},
+        test_util::{grammar, terminals},
+    };
+
+    #[test]
+    fn conflict_1() {
+        // S -> a a T b, T -> leaf | leaf leaf | ε
+        let grammar = grammar(&[
+            ("S", &[&["a", "a", "T", "b"]]),
+            ("T", &[&["leaf"], &["leaf", "leaf"], &[]]),
+        ]);
+        assert!(grammar.conflicts(2).is_empty());
+
+        let conflicts = grammar.conflicts(1);
+        assert_eq!(conflicts.len(), 1);
+
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.key, Key::new("T"));
+        assert_eq!(conflict.lookahead, terminals(&["leaf"]));
+        assert_eq!(
+            conflict
+                .examples
+                .iter()
+                .map(|example| example.id)
+                .collect::<Vec<_>>(),
+            vec![Id(0), Id(1)]
+        );
+
+        for example in &conflict.examples {
+            assert_eq!(example.prefix, terminals(&["a", "a"]));
+            assert_eq!(example.suffix, vec![Symbol::terminal(Key::new("b"))]);
+        }
+    }
+
+    #[test]
+    fn conflict_2() {
+        // S -> x A | y A A, A -> c | ε
+        // only after `y` is A followed by something starting with `c`
+        let grammar = grammar(&[
+            ("S", &[&["x", "A"], &["y", "A", "A"]]),
+            ("A", &[&["c"], &[]]),
+        ]);
+
+        let conflicts = grammar.conflicts(1);
+        assert_eq!(conflicts.len(), 1);
+
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.lookahead, terminals(&["c"]));
+
+        let [first, second] = &conflict.examples[..] else {
+            panic!("two examples")
+        };
+        assert_eq!((first.id, &first.prefix), (Id(0), &terminals(&["x"])));
+        assert_eq!((second.id, &second.prefix), (Id(1), &terminals(&["y"])));
+        assert_eq!(second.suffix, vec![Symbol::nonterminal(Key::new("A"))]);
+
+        let display = conflict.display(&grammar).to_string();
+        assert!(display.contains("`A#0` and `A#1` accept `c` here"));
+    }
+
+    #[test]
+    fn conflict_3() {
+        // S -> a a T b, T -> leaf | leaf leaf | ε, the search stops before reaching T
+        let grammar = grammar(&[
+            ("S", &[&["a", "a", "T", "b"]]),
+            ("T", &[&["leaf"], &["leaf", "leaf"], &[]]),
+        ]);
+
+        let conflicts = grammar.conflicts_within(1, &grammar.start, 1);
+        let [conflict] = &conflicts[..] else {
+            panic!("one conflict")
+        };
+        assert_eq!(conflict.ids, vec![Id(0), Id(1)]);
+        assert!(conflict.examples.is_empty());
+
+        let display = conflict.display(&grammar).to_string();
+        assert!(display.contains("`T#0` and `T#1` accept `leaf` here"));
+        assert_eq!(display.matches("no example found").count(), 2);
+    }
+}
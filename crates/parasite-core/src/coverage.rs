@@ -0,0 +1,314 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use owo_colors::OwoColorize;
+use thiserror::Error;
+
+use crate::{
+    grammar::{cmp_words_by_name, Grammar, Id, Key, Symbol, Terminal, Terminals},
+    parser::ParseError,
+    span::Token,
+    table::Table,
+};
+
+/// Why [`Coverage::record`] rejected a derivation, positions are indices into the input
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RecordError {
+    #[error("Derivation ended before {expected} at {position}")]
+    Ended { expected: Key, position: usize },
+    #[error("Production {key} {id} does not derive {expected} at {position}")]
+    Production {
+        key: Key,
+        id: Id,
+        expected: Key,
+        position: usize,
+    },
+    #[error("Derivation does not derive the input at {position}")]
+    Input { position: usize },
+    #[error("Derivation has {0} productions left after the input")]
+    Trailing(usize),
+}
+
+/// Counts how often the productions and the cells of the LL(k) table
+/// were used while parsing a corpus with [`Grammar::parse`]
+#[derive(Debug, Clone)]
+pub struct Coverage<'a> {
+    grammar: &'a Grammar,
+    k: usize,
+    table: Table,
+    productions: HashMap<(Key, Id), usize>,
+    cells: HashMap<(Key, Terminals), usize>,
+}
+
+impl<'a> Coverage<'a> {
+    pub fn new(grammar: &'a Grammar, k: usize) -> Self {
+        Self {
+            grammar,
+            k,
+            table: grammar.table(k),
+            productions: HashMap::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Parses `terminals` and records the productions and cells that were used
+    pub fn parse(&mut self, terminals: &[Terminal]) -> Result<Vec<(Key, Id)>, ParseError> {
+        let grammar = self.grammar;
+        let tokens = Token::indexed(terminals).map(Ok);
+        let applied = grammar.parse_with_table(&self.table, self.k, &grammar.start, tokens)?;
+        self.record(terminals, &applied)
+            .expect("the parser derives its input");
+        Ok(applied)
+    }
+
+    /// Records the result of `Grammar::parse` for `terminals`. Nothing is recorded
+    /// if `applied` is no leftmost derivation of `terminals` from the start symbol
+    pub fn record(
+        &mut self,
+        terminals: &[Terminal],
+        applied: &[(Key, Id)],
+    ) -> Result<(), RecordError> {
+        // replay the parse to know the lookahead of each production
+        let mut stack = vec![Symbol::nonterminal(self.grammar.start.clone())];
+        let mut applied = applied.iter();
+        let mut position = 0;
+        let mut cells = Vec::new();
+
+        while let Some(symbol) = stack.pop() {
+            match symbol {
+                Symbol::Epsilon => (),
+                Symbol::Terminal(terminal) => {
+                    if terminals.get(position) != Some(&terminal) {
+                        return Err(RecordError::Input { position });
+                    }
+                    position += 1;
+                }
+                Symbol::Nonterminal(expected) => {
+                    let Some((key, id)) = applied.next() else {
+                        return Err(RecordError::Ended {
+                            expected: expected.0,
+                            position,
+                        });
+                    };
+                    let symbols = self
+                        .grammar
+                        .get(key)
+                        .and_then(|rule| rule.get(id))
+                        .filter(|_| *key == expected.0)
+                        .ok_or_else(|| RecordError::Production {
+                            key: key.clone(),
+                            id: *id,
+                            expected: expected.0,
+                            position,
+                        })?;
+
+                    let mut peek = terminals[position..]
+                        .iter()
+                        .take(self.k)
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    if peek.len() < self.k {
+                        peek.push(Terminal::eoi());
+                    }
+
+                    let row = &self.table[key];
+                    let len = (1..=peek.len()).find(|len| row.contains_key(&peek[..*len]));
+                    cells.push((key, id, len.map(|len| peek[..len].to_vec())));
+
+                    stack.extend(symbols.iter().rev().cloned());
+                }
+            }
+        }
+
+        if position < terminals.len() {
+            return Err(RecordError::Input { position });
+        }
+        if !applied.as_slice().is_empty() {
+            return Err(RecordError::Trailing(applied.len()));
+        }
+
+        for (key, id, lookahead) in cells {
+            if let Some(lookahead) = lookahead {
+                *self.cells.entry((key.clone(), lookahead)).or_default() += 1;
+            }
+            *self.productions.entry((key.clone(), *id)).or_default() += 1;
+        }
+
+        Ok(())
+    }
+
+    /// How often the production was applied
+    pub fn count(&self, key: &Key, id: Id) -> usize {
+        self.productions
+            .get(&(key.clone(), id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The productions which were never applied
+    pub fn uncovered(&self) -> Vec<(Key, Id)> {
+        let mut uncovered = self
+            .grammar
+            .productions
+            .iter()
+            .flat_map(|(key, rule)| rule.keys().map(move |id| (key.clone(), *id)))
+            .filter(|production| !self.productions.contains_key(production))
+            .collect::<Vec<_>>();
+        uncovered.sort_by(|(a, a_id), (b, b_id)| a.cmp_by_name(b).then(a_id.cmp(b_id)));
+        uncovered
+    }
+
+    /// The cells of the table which were never looked up, with their production
+    pub fn unused(&self) -> Vec<(Key, Terminals, Id)> {
+        let mut unused = self
+            .table
+            .iter()
+            .flat_map(|(key, row)| {
+                row.iter()
+                    .map(move |(terminals, id)| (key.clone(), terminals.clone(), *id))
+            })
+            .filter(|(key, terminals, _)| {
+                !self.cells.contains_key(&(key.clone(), terminals.clone()))
+            })
+            .collect::<Vec<_>>();
+        unused.sort_by(|(a, a_words, a_id), (b, b_words, b_id)| {
+            a.cmp_by_name(b)
+                .then_with(|| cmp_words_by_name(a_words, b_words))
+                .then(a_id.cmp(b_id))
+        });
+        unused
+    }
+}
+
+impl fmt::Display for Coverage<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let grammar = self.grammar;
+        let (uncovered, unused) = (self.uncovered(), self.unused());
+
+        let productions = grammar
+            .productions
+            .values()
+            .map(|rule| rule.len())
+            .sum::<usize>();
+        let cells = self.table.iter().map(|(_, row)| row.len()).sum::<usize>();
+        writeln!(
+            f,
+            "{} {}/{productions} productions, {}/{cells} lookahead entries",
+            "Coverage".bold(),
+            productions - uncovered.len(),
+            cells - unused.len()
+        )?;
+
+        for (key, id) in &uncovered {
+            writeln!(
+                f,
+                "\t{} `{}`",
+                "uncovered".red(),
+                grammar.production_name(key, *id)
+            )?;
+        }
+
+        for (key, terminals, id) in &unused {
+            let terminals = terminals
+                .iter()
+                .map(|terminal| grammar.display_name(terminal))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                f,
+                "\t{} {terminals} for `{}`",
+                "unused".yellow(),
+                grammar.production_name(key, *id)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Id, Key, Terminal},
+    };
+
+    use super::{Coverage, RecordError};
+
+    #[test]
+    fn coverage_1() {
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+        let option = Key::of::<Option<bool>>();
+
+        let mut coverage = Coverage::new(&grammar, 1);
+        coverage.parse(&[uint.clone(), uint.clone()]).unwrap();
+        assert!(coverage.parse(std::slice::from_ref(&boolean)).is_err());
+
+        assert_eq!(
+            coverage.count(&Key::of::<Vec<(u8, Option<bool>)>>(), Id(0)),
+            2
+        );
+        assert_eq!(coverage.count(&option, Id(1)), 2);
+
+        // `Some` was never parsed, so its lookahead was never seen
+        assert_eq!(coverage.uncovered(), vec![(option.clone(), Id(0))]);
+        assert_eq!(
+            coverage.unused(),
+            vec![(option, vec![boolean.clone()], Id(0))]
+        );
+
+        coverage.parse(&[uint, boolean]).unwrap();
+        assert!(coverage.uncovered().is_empty());
+        assert!(coverage.to_string().contains("productions"));
+    }
+
+    #[test]
+    fn coverage_2() {
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let input = [uint.clone()];
+        let applied = grammar.parse(1, &input).unwrap();
+        let mut coverage = Coverage::new(&grammar, 1);
+
+        assert!(matches!(
+            coverage.record(&input, &applied[..1]),
+            Err(RecordError::Ended { position: 0, .. })
+        ));
+
+        let mut unknown = applied.clone();
+        unknown[0].1 = Id(7);
+        assert!(matches!(
+            coverage.record(&input, &unknown),
+            Err(RecordError::Production { id: Id(7), .. })
+        ));
+
+        let mut trailing = applied.clone();
+        trailing.push(applied[0].clone());
+        assert_eq!(
+            coverage.record(&input, &trailing),
+            Err(RecordError::Trailing(1))
+        );
+
+        assert_eq!(
+            coverage.record(&[uint.clone(), uint], &applied),
+            Err(RecordError::Input { position: 1 })
+        );
+
+        // rejected derivations are not counted
+        let (key, id) = &applied[0];
+        assert_eq!(coverage.count(key, *id), 0);
+        coverage.record(&input, &applied).unwrap();
+        assert_eq!(coverage.count(key, *id), 1);
+    }
+}
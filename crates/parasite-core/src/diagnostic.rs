@@ -0,0 +1,329 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use owo_colors::{OwoColorize, Style};
+
+use crate::{
+    combinators::DELIMITED_BY,
+    grammar::{Grammar, Terminal},
+    parser::ParseError,
+    span::{SourceMap, Span},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// An error message with labelled spans of the source, to be rendered as a snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Label::new(span, message));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Renders the diagnostic with the source lines of its labels,
+    /// `colored` can be disabled to get plain text for logs
+    pub fn render(&self, map: &SourceMap, colored: bool) -> String {
+        let paint = |text: &str, style: Style| {
+            if colored {
+                text.style(style).to_string()
+            } else {
+                text.to_string()
+            }
+        };
+        let error = Style::new().red().bold();
+        let info = Style::new().blue().bold();
+
+        let mut out = format!("{}: {}\n", paint("error", error), self.message);
+
+        // spans of tokens produced at or after the end of the input point at its end
+        let clamp = |label: &Label| {
+            let span = Span::new(
+                label.span.start.min(map.len()),
+                label.span.end.min(map.len()),
+            );
+            Label::new(span, label.message.clone())
+        };
+        let mut labels = self
+            .primary
+            .iter()
+            .map(|label| (clamp(label), '^', error))
+            .chain(self.secondary.iter().map(|label| (clamp(label), '-', info)))
+            .collect::<Vec<_>>();
+
+        let width = labels
+            .iter()
+            .map(|(label, _, _)| map.location(label.span.start).line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = paint(&format!("{} |", " ".repeat(width)), info);
+
+        if let Some(primary) = &self.primary {
+            let location = map.location(primary.span.start);
+            out += &format!("{}{} {location}\n", " ".repeat(width), paint("-->", info));
+        }
+
+        labels.sort_by_key(|(label, _, _)| label.span.start);
+        let mut last_line = None;
+
+        for (label, marker, style) in labels {
+            let location = map.location(label.span.start);
+            let line = map.line(location.line);
+
+            if last_line != Some(location.line) {
+                out += &format!("{gutter}\n");
+                let number = format!("{:>width$} |", location.line);
+                out += &format!("{} {line}\n", paint(&number, info));
+                last_line = Some(location.line);
+            }
+
+            // underline until the end of the span or its first line
+            let line_end = map.line_span(location.line).end;
+            let end = label.span.end.min(line_end).max(label.span.start);
+            let len = map.source()[label.span.start.min(line_end)..end]
+                .chars()
+                .count()
+                .max(1);
+
+            let underline = marker.to_string().repeat(len);
+            let underline = format!("{underline} {}", label.message);
+            out += &format!(
+                "{gutter} {}{}\n",
+                " ".repeat(location.column - 1),
+                paint(&underline, style)
+            );
+        }
+
+        for note in &self.notes {
+            out += &format!("{} {} {note}\n", " ".repeat(width), paint("=", info));
+        }
+
+        out
+    }
+}
+
+impl Grammar {
+    /// Describes a parse error using the display names of the terminals
+    pub fn diagnostic<E: Display>(&self, error: &ParseError<E>) -> Diagnostic {
+        match error {
+            ParseError::Unexpected {
+                terminal,
+                expected,
+                label,
+                span,
+                open,
+            } => {
+                let found = self.display_name(terminal);
+                let mut diagnostic = Diagnostic::new(format!("unexpected {found}"))
+                    .with_primary(*span, "unexpected here")
+                    .with_note(match label {
+                        Some(label) => format!("expected {label}"),
+                        None => format!("expected {}", self.expected_list(terminal, expected)),
+                    });
+
+                for (key, id, start) in open {
+                    let origin = self.production(key, *id).and_then(|p| p.origin.as_deref());
+                    if start.start < span.start && origin == Some(DELIMITED_BY) {
+                        diagnostic = diagnostic.with_secondary(*start, "unclosed delimiter");
+                    }
+                }
+
+                // otherwise point at the start of the innermost production around the error
+                if diagnostic.secondary.is_empty() {
+                    if let Some((key, id, start)) = open
+                        .iter()
+                        .rev()
+                        .find(|(_, _, start)| start.start < span.start)
+                    {
+                        let name = self.production_name(key, *id);
+                        diagnostic =
+                            diagnostic.with_secondary(*start, format!("while parsing `{name}`"));
+                    }
+                }
+
+                diagnostic
+            }
+            ParseError::Lexer(e) => Diagnostic::new(e.to_string()),
+            ParseError::UnknownEntry(key) => {
+                Diagnostic::new(format!("`{key}` is no nonterminal of the grammar"))
+            }
+        }
+    }
+}
+
+impl Grammar {
+    /// Lists the expected lookahead strings by their first terminal. Longer
+    /// strings are only spelled out if they start with the unexpected terminal
+    fn expected_list(&self, found: &Terminal, expected: &[Vec<Terminal>]) -> String {
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+
+        for terminals in expected {
+            if let Some((first, rest)) = terminals.split_first() {
+                let group = groups.entry(first.clone()).or_default();
+                if first == found && !rest.is_empty() {
+                    let rest = rest.iter().map(|t| self.display_name(t));
+                    group.push(rest.collect::<Vec<_>>().join(" "));
+                }
+            }
+        }
+
+        let mut items = Vec::new();
+        for (first, mut rests) in groups {
+            rests.sort();
+            rests.dedup();
+
+            let item = if rests.is_empty() {
+                self.display_name(&first)
+            } else {
+                let first = self.display_name(&first);
+                format!("{first} followed by {}", rests.join(" or "))
+            };
+
+            items.push(item);
+        }
+
+        items.sort();
+        items.dedup();
+
+        match items.split_last() {
+            Some((last, init)) if !init.is_empty() => format!("{} or {last}", init.join(", ")),
+            Some((last, _)) => last.clone(),
+            None => "nothing".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        combinators::{DelimitedBy, Identifier, Just, NewLine},
+        grammar::{Grammar, Key, Terminal},
+        span::{SourceMap, Span, Token},
+    };
+
+    use super::{Diagnostic, Label};
+
+    type Group = DelimitedBy<Just<'('>, Just<')'>, Vec<u8>>;
+
+    #[test]
+    fn diagnostic_1() {
+        let mut grammar = Grammar::new(Key::of::<Group>());
+        let mut stack = Vec::new();
+
+        Group::generate(&mut grammar, &mut stack);
+
+        let open = Terminal(Key::of::<Just<'('>>());
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let map = SourceMap::new("(1 2\n  true");
+        let tokens = vec![
+            Ok::<_, String>(Token::new(open, 0..1)),
+            Ok(Token::new(uint.clone(), 1..2)),
+            Ok(Token::new(uint, 3..4)),
+            Ok(Token::new(boolean, 7..11)),
+        ];
+
+        let err = grammar.parse_tokens(1, tokens).unwrap_err();
+        let diagnostic = grammar.diagnostic(&err);
+
+        assert_eq!(
+            diagnostic.primary,
+            Some(Label::new(Span::new(7, 11), "unexpected here"))
+        );
+        assert_eq!(
+            diagnostic.secondary,
+            vec![Label::new(Span::new(0, 1), "unclosed delimiter")]
+        );
+
+        let rendered = diagnostic.render(&map, false);
+        let expected = "error: unexpected `bool`
+ --> 2:3
+  |
+1 | (1 2
+  | - unclosed delimiter
+  |
+2 |   true
+  |   ^^^^ unexpected here
+  = expected `')'` or `u8`
+";
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn diagnostic_2() {
+        type Line = (Option<Just<'a'>>, Identifier, NewLine);
+
+        let mut grammar = Grammar::new(Key::of::<Line>());
+        let mut stack = Vec::new();
+
+        Line::generate(&mut grammar, &mut stack);
+
+        let newline = Terminal(Key::of::<NewLine>());
+        let err = grammar
+            .parse_tokens(2, [Ok::<_, String>(Token::new(newline, 0..1))])
+            .unwrap_err();
+
+        let diagnostic = grammar.diagnostic(&err);
+        assert_eq!(diagnostic.message, "unexpected newline");
+        assert_eq!(diagnostic.notes, vec!["expected `'a'` or identifier"]);
+    }
+
+    #[test]
+    fn diagnostic_eoi() {
+        let map = SourceMap::new("(1\n2");
+
+        // the end of the input is reported right after the last token
+        let rendered = Diagnostic::new("unexpected end of input")
+            .with_primary(Span::empty(4), "unexpected here")
+            .render(&map, false);
+        assert_eq!(
+            rendered,
+            "error: unexpected end of input\n --> 2:2\n  |\n2 | 2\n  |  ^ unexpected here\n"
+        );
+
+        let past = Diagnostic::new("unexpected end of input")
+            .with_primary(Span::new(7, 9), "unexpected here")
+            .render(&map, false);
+        assert_eq!(past, rendered);
+    }
+}
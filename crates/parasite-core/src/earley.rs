@@ -0,0 +1,670 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    grammar::{cmp_words_by_name, Grammar, Id, Key, Terminal},
+    intern::{Interned, Sym, SymbolId},
+    parser::ParseError,
+    span::Span,
+};
+
+/// How [`Forest::tree`] picks one derivation where the forest has several
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Policy {
+    /// The production with the smallest id, then the longest prefix before
+    /// the last symbol, so that `E -> E + E` groups to the left
+    #[default]
+    Left,
+    /// The production with the smallest id, then the shortest prefix before
+    /// the last symbol, so that `E -> E + E` groups to the right
+    Right,
+}
+
+/// A child of a packed node, tokens are indices into the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Child<'a> {
+    Token(usize),
+    Node(&'a Key, usize, usize),
+    /// The first `dot` symbols of the production `id` of `key` deriving the input
+    /// from `start` to `end`, shared by all derivations which continue them
+    Prefix {
+        key: &'a Key,
+        id: Id,
+        dot: usize,
+        start: usize,
+        end: usize,
+    },
+}
+
+/// A node of the forest, see [`Child`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Token(usize),
+    Symbol(SymbolId, usize, usize),
+    Intermediate {
+        rule: SymbolId,
+        production: usize,
+        dot: usize,
+        start: usize,
+        end: usize,
+    },
+}
+
+/// One way to derive the input of a node: by a prefix of the production and its next symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packed {
+    pub id: Id,
+    /// At most two, the prefix is left out when it is empty
+    children: Vec<Node>,
+}
+
+/// The binarised shared packed parse forest of all derivations of the input.
+/// Symbol nodes are a nonterminal with the range of the input it derives,
+/// intermediate nodes a prefix of a production with its range. Each node has
+/// one packed node per way to split its range into the prefix before its
+/// last symbol and that symbol, so the forest stays cubic in the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forest {
+    nonterminals: Vec<Key>,
+    /// The ids of the productions of each nonterminal, intermediate nodes refer to their index
+    ids: Vec<Vec<Id>>,
+    root: (SymbolId, usize, usize),
+    nodes: HashMap<Node, Vec<Packed>>,
+}
+
+impl Forest {
+    /// The entry and the length of the input
+    pub fn root(&self) -> (&Key, usize) {
+        (&self.nonterminals[self.root.0.index()], self.root.2)
+    }
+
+    /// The number of symbol and intermediate nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The packed nodes of `key` deriving the input from `start` to `end`
+    pub fn packed(&self, key: &Key, start: usize, end: usize) -> &[Packed] {
+        match self.symbol(key) {
+            Some(symbol) => self.packed_of_node(Node::Symbol(symbol, start, end)),
+            None => &[],
+        }
+    }
+
+    /// The packed nodes of a child, tokens have none
+    pub fn packed_of(&self, child: Child) -> &[Packed] {
+        let node = match child {
+            Child::Token(_) => return &[],
+            Child::Node(key, start, end) => return self.packed(key, start, end),
+            Child::Prefix {
+                key,
+                id,
+                dot,
+                start,
+                end,
+            } => {
+                let Some(rule) = self.symbol(key) else {
+                    return &[];
+                };
+                let Some(production) = self.ids[rule.index()].iter().position(|other| *other == id)
+                else {
+                    return &[];
+                };
+                Node::Intermediate {
+                    rule,
+                    production,
+                    dot,
+                    start,
+                    end,
+                }
+            }
+        };
+        self.packed_of_node(node)
+    }
+
+    pub fn children<'a>(&'a self, packed: &'a Packed) -> impl Iterator<Item = Child<'a>> + 'a {
+        packed.children.iter().map(|node| match *node {
+            Node::Token(at) => Child::Token(at),
+            Node::Symbol(symbol, start, end) => {
+                Child::Node(&self.nonterminals[symbol.index()], start, end)
+            }
+            Node::Intermediate {
+                rule,
+                production,
+                dot,
+                start,
+                end,
+            } => Child::Prefix {
+                key: &self.nonterminals[rule.index()],
+                id: self.ids[rule.index()][production],
+                dot,
+                start,
+                end,
+            },
+        })
+    }
+
+    fn symbol(&self, key: &Key) -> Option<SymbolId> {
+        self.nonterminals
+            .iter()
+            .position(|other| other == key)
+            .map(|index| SymbolId(index as u32))
+    }
+
+    fn packed_of_node(&self, node: Node) -> &[Packed] {
+        self.nodes.get(&node).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether some part of the input has more than one derivation
+    pub fn is_ambiguous(&self) -> bool {
+        self.nodes.values().any(|packed| packed.len() > 1)
+    }
+
+    /// The applied productions of one tree of the forest in the order of a leftmost derivation
+    pub fn tree(&self, policy: Policy) -> Vec<(Key, Id)> {
+        let mut applied = Vec::new();
+        let mut active = HashSet::new();
+        let (rule, start, end) = self.root;
+        let found = self.subtree(
+            Node::Symbol(rule, start, end),
+            policy,
+            &mut active,
+            &mut applied,
+        );
+        debug_assert!(found, "the root has a finite derivation");
+        applied
+    }
+
+    /// Appends a derivation of `node` to `applied`, avoiding the symbol nodes in `active`
+    /// which would only lead to cyclic derivations like `A -> A`
+    fn subtree(
+        &self,
+        node: Node,
+        policy: Policy,
+        active: &mut HashSet<Node>,
+        applied: &mut Vec<(Key, Id)>,
+    ) -> bool {
+        let symbol = match node {
+            Node::Token(_) => return true,
+            Node::Symbol(symbol, _, _) => Some(symbol),
+            Node::Intermediate { .. } => None,
+        };
+        if symbol.is_some() && !active.insert(node) {
+            return false;
+        }
+
+        // the last symbol starts where the prefix ends
+        let mut packed = self.nodes[&node].iter().collect::<Vec<_>>();
+        packed.sort_by_key(|packed| {
+            let split = match packed.children.as_slice() {
+                [_, Node::Token(start)] | [_, Node::Symbol(_, start, _)] => *start,
+                _ => 0,
+            };
+            let split = match policy {
+                Policy::Left => usize::MAX - split,
+                Policy::Right => split,
+            };
+            (packed.id, split)
+        });
+
+        let len = applied.len();
+        let found = packed.into_iter().any(|packed| {
+            applied.truncate(len);
+            if let Some(symbol) = symbol {
+                applied.push((self.nonterminals[symbol.index()].clone(), packed.id));
+            }
+
+            packed
+                .children
+                .iter()
+                .all(|child| self.subtree(*child, policy, active, applied))
+        });
+
+        if symbol.is_some() {
+            active.remove(&node);
+        }
+        found
+    }
+}
+
+/// An Earley item, a production with a position in it and where it started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    rule: SymbolId,
+    production: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl Grammar {
+    /// Parses `terminals` with an Earley parser, which accepts any grammar,
+    /// and picks one of the derivations with `policy`
+    pub fn parse_earley(
+        &self,
+        terminals: &[Terminal],
+        policy: Policy,
+    ) -> Result<Vec<(Key, Id)>, ParseError> {
+        Ok(self.forest(terminals)?.tree(policy))
+    }
+
+    /// All derivations of `terminals` from the start symbol
+    pub fn forest(&self, terminals: &[Terminal]) -> Result<Forest, ParseError> {
+        self.forest_from(&self.start, terminals)
+    }
+
+    pub fn forest_from(&self, entry: &Key, terminals: &[Terminal]) -> Result<Forest, ParseError> {
+        self.check_entry(entry)?;
+
+        let interned = self.interned();
+        let entry = interned.nonterminal_id(entry);
+        let nullable = interned
+            .first_k(1)
+            .iter()
+            .map(|rule| {
+                rule.iter()
+                    .any(|(_, words)| words.iter().any(|word| word.is_empty()))
+            })
+            .collect::<Vec<_>>();
+
+        let input = terminals
+            .iter()
+            .map(|terminal| interned.terminal_id(terminal))
+            .collect::<Vec<_>>();
+
+        let symbols = |item: &Item| &interned.rules[item.rule.index()][item.production].1;
+
+        let mut sets = vec![Vec::<Item>::new(); input.len() + 1];
+        let mut seen = vec![HashSet::<Item>::new(); input.len() + 1];
+        // the productions which derive the input between two positions
+        let mut completed = HashMap::<(SymbolId, usize, usize), Vec<usize>>::new();
+
+        let add = |sets: &mut Vec<Vec<Item>>, seen: &mut Vec<HashSet<Item>>, at: usize, item| {
+            if seen[at].insert(item) {
+                sets[at].push(item);
+            }
+        };
+
+        for production in 0..interned.rules[entry.index()].len() {
+            let item = Item {
+                rule: entry,
+                production,
+                dot: 0,
+                origin: 0,
+            };
+            add(&mut sets, &mut seen, 0, item);
+        }
+
+        for at in 0..=input.len() {
+            let mut next = 0;
+            while next < sets[at].len() {
+                let item = sets[at][next];
+                next += 1;
+
+                let advanced = Item {
+                    dot: item.dot + 1,
+                    ..item
+                };
+
+                match symbols(&item).get(item.dot) {
+                    None => {
+                        completed
+                            .entry((item.rule, item.origin, at))
+                            .or_default()
+                            .push(item.production);
+
+                        for index in 0..sets[item.origin].len() {
+                            let waiting = sets[item.origin][index];
+                            if symbols(&waiting).get(waiting.dot)
+                                == Some(&Sym::Nonterminal(item.rule))
+                            {
+                                let waiting = Item {
+                                    dot: waiting.dot + 1,
+                                    ..waiting
+                                };
+                                add(&mut sets, &mut seen, at, waiting);
+                            }
+                        }
+                    }
+                    Some(Sym::Nonterminal(predicted)) => {
+                        for production in 0..interned.rules[predicted.index()].len() {
+                            let item = Item {
+                                rule: *predicted,
+                                production,
+                                dot: 0,
+                                origin: at,
+                            };
+                            add(&mut sets, &mut seen, at, item);
+                        }
+
+                        // nullable symbols may be skipped right away, as they complete
+                        // in this set and would miss the items waiting for them
+                        if nullable[predicted.index()] {
+                            add(&mut sets, &mut seen, at, advanced);
+                        }
+                    }
+                    Some(Sym::Terminal(terminal)) => {
+                        if input.get(at) == Some(&Some(*terminal)) {
+                            add(&mut sets, &mut seen, at + 1, advanced);
+                        }
+                    }
+                }
+            }
+
+            let stuck = at < input.len() && sets[at + 1].is_empty();
+            let rejected = at == input.len() && !completed.contains_key(&(entry, 0, at));
+            if stuck || rejected {
+                let mut expected = sets[at]
+                    .iter()
+                    .filter_map(|item| match symbols(item).get(item.dot) {
+                        Some(Sym::Terminal(terminal)) => {
+                            Some(vec![interned.terminals[terminal.index()].clone()])
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                if completed.contains_key(&(entry, 0, at)) {
+                    expected.push(vec![Terminal::eoi()]);
+                }
+                expected.sort_by(|a, b| cmp_words_by_name(a, b));
+                expected.dedup();
+
+                return Err(ParseError::Unexpected {
+                    terminal: terminals.get(at).cloned().unwrap_or_else(Terminal::eoi),
+                    expected,
+                    label: None,
+                    span: Span::new(at, (at + 1).min(input.len())),
+                    open: Vec::new(),
+                });
+            }
+        }
+
+        // only the nodes reachable from the root make it into the forest
+        let chart = Chart {
+            interned,
+            input: &input,
+            seen: &seen,
+            completed: &completed,
+        };
+        let root = (entry, 0, input.len());
+        let mut nodes = HashMap::new();
+        let mut pending = vec![Node::Symbol(entry, 0, input.len())];
+
+        while let Some(node) = pending.pop() {
+            if nodes.contains_key(&node) {
+                continue;
+            }
+
+            let mut packed = Vec::new();
+            let mut split = |rule: SymbolId, production: usize, dot, start, end| {
+                let id = interned.rules[rule.index()][production].0;
+                for children in chart.splits(rule, production, dot, start, end) {
+                    pending.extend(&children);
+                    packed.push(Packed { id, children });
+                }
+            };
+            match node {
+                Node::Token(_) => continue,
+                Node::Symbol(rule, start, end) => {
+                    for &production in &completed[&(rule, start, end)] {
+                        let dot = interned.rules[rule.index()][production].1.len();
+                        split(rule, production, dot, start, end);
+                    }
+                }
+                Node::Intermediate {
+                    rule,
+                    production,
+                    dot,
+                    start,
+                    end,
+                } => split(rule, production, dot, start, end),
+            }
+            nodes.insert(node, packed);
+        }
+
+        Ok(Forest {
+            nonterminals: interned.nonterminals.clone(),
+            ids: interned
+                .rules
+                .iter()
+                .map(|rule| rule.iter().map(|(id, _)| *id).collect())
+                .collect(),
+            root,
+            nodes,
+        })
+    }
+}
+
+/// The Earley sets of a recognised input
+struct Chart<'a> {
+    interned: &'a Interned,
+    input: &'a [Option<SymbolId>],
+    seen: &'a [HashSet<Item>],
+    completed: &'a HashMap<(SymbolId, usize, usize), Vec<usize>>,
+}
+
+impl Chart<'_> {
+    /// The ways the first `dot` symbols of a production derive the input from `start` to `end`,
+    /// split into the node of the symbols before the last one and the node of the last one
+    fn splits(
+        &self,
+        rule: SymbolId,
+        production: usize,
+        dot: usize,
+        start: usize,
+        end: usize,
+    ) -> Vec<Vec<Node>> {
+        let symbols = &self.interned.rules[rule.index()][production].1;
+        let node = |symbol: &Sym, start, end| match symbol {
+            Sym::Terminal(_) => Node::Token(start),
+            Sym::Nonterminal(nonterminal) => Node::Symbol(*nonterminal, start, end),
+        };
+
+        let Some(last) = dot.checked_sub(1).map(|last| &symbols[last]) else {
+            return match start == end {
+                true => vec![Vec::new()],
+                false => Vec::new(),
+            };
+        };
+
+        // the item before the last symbol is in the set where the last symbol starts,
+        // as its prefix derives the input up to there
+        let prefix = |split: usize| match dot - 1 {
+            0 => split == start,
+            dot => self.seen[split].contains(&Item {
+                rule,
+                production,
+                dot,
+                origin: start,
+            }),
+        };
+
+        let splits = match last {
+            Sym::Terminal(terminal) => {
+                match start < end && self.input[end - 1] == Some(*terminal) && prefix(end - 1) {
+                    true => vec![end - 1],
+                    false => Vec::new(),
+                }
+            }
+            Sym::Nonterminal(nonterminal) => (start..=end)
+                .filter(|split| self.completed.contains_key(&(*nonterminal, *split, end)))
+                .filter(|split| prefix(*split))
+                .collect(),
+        };
+
+        splits
+            .into_iter()
+            .map(|split| {
+                let last = node(last, split, end);
+                match dot - 1 {
+                    0 => vec![last],
+                    1 => vec![node(&symbols[0], start, split), last],
+                    dot => vec![
+                        Node::Intermediate {
+                            rule,
+                            production,
+                            dot,
+                            start,
+                            end: split,
+                        },
+                        last,
+                    ],
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Id, Key, Terminal},
+        parser::ParseError,
+        test_util::{grammar, terminals},
+    };
+
+    use super::{Child, Policy};
+
+    #[test]
+    fn earley_1() {
+        // E -> E plus E | n
+        let grammar = grammar(&[("E", &[&["E", "plus", "E"], &["n"]])]);
+        let input = terminals(&["n", "plus", "n", "plus", "n"]);
+        let e = Key::new("E");
+
+        let forest = grammar.forest(&input).unwrap();
+        assert!(forest.is_ambiguous());
+
+        let packed = forest.packed(&e, 0, 5);
+        assert_eq!(packed.len(), 2);
+        let mut splits = packed
+            .iter()
+            .map(|packed| forest.children(packed).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        splits.sort();
+        let prefix = |end| Child::Prefix {
+            key: &e,
+            id: Id(0),
+            dot: 2,
+            start: 0,
+            end,
+        };
+        assert_eq!(
+            splits,
+            vec![
+                vec![prefix(2), Child::Node(&e, 2, 5)],
+                vec![prefix(4), Child::Node(&e, 4, 5)],
+            ]
+        );
+
+        // the prefix `E plus` is shared by the derivations continuing it
+        let packed = forest.packed_of(prefix(4));
+        assert_eq!(packed.len(), 1);
+        assert_eq!(
+            forest.children(&packed[0]).collect::<Vec<_>>(),
+            vec![Child::Node(&e, 0, 3), Child::Token(3)]
+        );
+        assert_eq!(forest.packed(&e, 0, 3).len(), 1);
+
+        assert_eq!(
+            forest.tree(Policy::Left),
+            vec![
+                (e.clone(), Id(0)),
+                (e.clone(), Id(0)),
+                (e.clone(), Id(1)),
+                (e.clone(), Id(1)),
+                (e.clone(), Id(1)),
+            ]
+        );
+        assert_eq!(
+            grammar.parse_earley(&input, Policy::Right).unwrap(),
+            vec![
+                (e.clone(), Id(0)),
+                (e.clone(), Id(1)),
+                (e.clone(), Id(0)),
+                (e.clone(), Id(1)),
+                (e, Id(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn earley_2() {
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+        let input = [uint.clone(), uint.clone(), boolean, uint];
+
+        let forest = grammar.forest(&input).unwrap();
+        assert!(!forest.is_ambiguous());
+        assert_eq!(
+            forest.tree(Policy::default()),
+            grammar.parse(1, &input).unwrap()
+        );
+    }
+
+    #[test]
+    fn earley_3() {
+        // A -> A | B a, B -> ε | B b
+        let grammar = grammar(&[("A", &[&["A"], &["B", "a"]]), ("B", &[&[], &["B", "b"]])]);
+        let (a, b) = (Key::new("A"), Key::new("B"));
+
+        assert_eq!(
+            grammar
+                .parse_earley(&terminals(&["b", "b", "a"]), Policy::Left)
+                .unwrap(),
+            vec![
+                (a.clone(), Id(1)),
+                (b.clone(), Id(1)),
+                (b.clone(), Id(1)),
+                (b, Id(0)),
+            ]
+        );
+        assert!(grammar.forest(&terminals(&["a"])).unwrap().is_ambiguous());
+
+        let err = grammar.forest(&terminals(&["b", "a", "a"])).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::Unexpected { terminal, expected, .. }
+                if terminal == Terminal::from(Key::new("a")) && expected == vec![vec![Terminal::eoi()]]
+        ));
+
+        let err = grammar.forest(&terminals(&["b"])).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::Unexpected { terminal, expected, .. }
+                if terminal.is_eoi() && expected == vec![terminals(&["a"]), terminals(&["b"])]
+        ));
+    }
+
+    #[test]
+    fn earley_4() {
+        // E -> E E E | n, the forest holds one packed node per split of the last E
+        let grammar = grammar(&[("E", &[&["E", "E", "E"], &["n"]])]);
+        let input = terminals(&["n"; 9]);
+        let e = Key::new("E");
+
+        let forest = grammar.forest(&input).unwrap();
+        let mut splits = forest
+            .packed(&e, 0, 9)
+            .iter()
+            .map(|packed| forest.children(packed).last().unwrap())
+            .collect::<Vec<_>>();
+        splits.sort();
+        assert_eq!(splits, [2, 4, 6, 8].map(|start| Child::Node(&e, start, 9)));
+
+        // 9 leaves and 4 nodes with 3 children each
+        assert_eq!(
+            grammar.parse_earley(&input, Policy::Left).unwrap().len(),
+            13
+        );
+    }
+}
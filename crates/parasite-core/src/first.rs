@@ -1,8 +1,11 @@
 use owo_colors::OwoColorize;
 
-use crate::grammar::{Grammar, Id, Key, Symbol, Terminals};
+use crate::{
+    grammar::{Grammar, Id, Key, Terminals},
+    intern::{Interned, Sym, SymbolId, TerminalSet, Word, WordSet, Worklist},
+};
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashMap, HashSet},
     fmt,
     ops::Index,
 };
@@ -61,97 +64,309 @@ impl Index<&Key> for FirstTable {
     }
 }
 
-type FirstItem = (Id, (Terminals, VecDeque<Symbol>));
+/// The k-truncated concatenation `left ⊕k right`: every word of `left` followed by every
+/// word of `right`, cut after `k` terminals. Words which are already `k` long stay as they are,
+/// unless `right` is empty
+pub(crate) fn concat_k<'a>(
+    left: impl IntoIterator<Item = &'a Word>,
+    right: &WordSet,
+    k: usize,
+) -> WordSet {
+    let mut words = WordSet::default();
+    if right.is_empty() {
+        return words;
+    }
 
-impl Grammar {
-    fn first_k_of(
-        &self,
-        k: usize,
-        of: Key,
-        queue: &mut VecDeque<Key>,
-        cache: &mut HashMap<Key, Vec<FirstItem>>,
-    ) {
-        if cache[&of]
-            .iter()
-            .all(|(_, (terminals, symbols))| terminals.len() >= k || symbols.is_empty())
-        {
-            return;
+    for word in left {
+        if word.len() >= k {
+            words.insert(word.clone());
+            continue;
         }
 
-        let mut items = Vec::new();
+        for suffix in right {
+            let mut word = word.clone();
+            word.extend(suffix.iter().take(k - word.len()).copied());
+            words.insert(word);
+        }
+    }
 
-        for (id, (mut terminals, mut symbols)) in cache[&of].clone() {
-            while let Some(symbol) = symbols.pop_front() {
-                match symbol {
-                    Symbol::Epsilon => (),
-                    Symbol::Terminal(terminal) => terminals.push(terminal),
-                    Symbol::Nonterminal(nonterminal) => {
-                        let key = nonterminal.0;
+    words
+}
 
-                        let mut to_push = Vec::new();
-                        for (_, (terms, syms)) in &cache[&key] {
-                            let mut terminals = terminals.clone();
-                            terminals.append(&mut terms.clone());
+pub(crate) fn all_productive(symbols: &[Sym], productive: &[bool]) -> bool {
+    symbols.iter().all(|symbol| match symbol {
+        Sym::Terminal(_) => true,
+        Sym::Nonterminal(nonterminal) => productive[nonterminal.index()],
+    })
+}
 
-                            let mut syms = syms.clone();
-                            syms.append(&mut symbols.clone());
+impl Interned {
+    /// FIRST_k of every production, indexed like `rules`
+    pub(crate) fn first_k(&self, k: usize) -> Vec<Vec<(Id, WordSet)>> {
+        if k == 1 {
+            return self.first_1();
+        }
 
-                            to_push.push((id, (terminals, syms)));
-                        }
-                        if let Some((_, (terms, syms))) = to_push.pop() {
-                            terminals = terms;
-                            symbols = syms;
-                        }
+        // FIRST_k of every prefix of every production, the words added to a nonterminal are
+        // only concatenated with the prefixes before its invocations and passed on from there
+        let mut prefixes = self
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.iter()
+                    .map(|(_, symbols)| vec![WordSet::default(); symbols.len() + 1])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut invocations = vec![Vec::new(); self.rules.len()];
+        for (of, rule) in self.rules.iter().enumerate() {
+            for (production, (_, symbols)) in rule.iter().enumerate() {
+                for (position, symbol) in symbols.iter().enumerate() {
+                    if let Sym::Nonterminal(invoked) = symbol {
+                        invocations[invoked.index()].push((of, production, position));
+                    }
+                }
+            }
+        }
 
-                        for item in to_push {
-                            items.push(item);
-                        }
+        let mut first = vec![WordSet::default(); self.rules.len()];
+        let mut added = vec![Vec::new(); self.rules.len()];
+        let mut worklist = Worklist::empty(self.rules.len());
+
+        for (of, rule) in self.rules.iter().enumerate() {
+            for (production, (_, symbols)) in rule.iter().enumerate() {
+                let prefixes = &mut prefixes[of][production];
+                for word in self.pass_on(k, symbols, prefixes, &first, 0, [Word::new()]) {
+                    if first[of].insert(word.clone()) {
+                        added[of].push(word);
+                        worklist.push(SymbolId(of as u32));
+                    }
+                }
+            }
+        }
 
-                        queue.push_back(of.clone());
-                        break;
+        while let Some(invoked) = worklist.pop() {
+            let words = std::mem::take(&mut added[invoked.index()])
+                .into_iter()
+                .collect::<WordSet>();
+
+            for &(of, production, position) in &invocations[invoked.index()] {
+                let symbols = &self.rules[of][production].1;
+                let prefixes = &mut prefixes[of][production];
+                let words = concat_k(&prefixes[position], &words, k);
+
+                for word in self.pass_on(k, symbols, prefixes, &first, position + 1, words) {
+                    if first[of].insert(word.clone()) {
+                        added[of].push(word);
+                        worklist.push(SymbolId(of as u32));
                     }
                 }
+            }
+        }
+
+        self.rules
+            .iter()
+            .zip(prefixes)
+            .map(|(rule, mut prefixes)| {
+                rule.iter()
+                    .zip(&mut prefixes)
+                    .map(|((id, _), prefixes)| (*id, prefixes.pop().unwrap()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Adds `words` to the prefix of `symbols` ending before `position` and passes the new ones
+    /// on to the longer prefixes, returns the new words of the whole string. Only the prefixes
+    /// before nonterminals and the whole string are kept, as only these are looked up again
+    fn pass_on(
+        &self,
+        k: usize,
+        symbols: &[Sym],
+        prefixes: &mut [WordSet],
+        first: &[WordSet],
+        position: usize,
+        words: impl IntoIterator<Item = Word>,
+    ) -> Vec<Word> {
+        let mut add = |position: usize, words: Vec<Word>| match symbols.get(position) {
+            None | Some(Sym::Nonterminal(_)) => words
+                .into_iter()
+                .filter(|word| prefixes[position].insert(word.clone()))
+                .collect(),
+            Some(Sym::Terminal(_)) => words,
+        };
+
+        let mut words = add(position, words.into_iter().collect());
+        for (position, symbol) in symbols.iter().enumerate().skip(position) {
+            if words.is_empty() {
+                break;
+            }
 
-                if terminals.len() >= k {
-                    terminals.truncate(k);
-                    break;
+            match symbol {
+                Sym::Terminal(terminal) => words
+                    .iter_mut()
+                    .filter(|word| word.len() < k)
+                    .for_each(|word| word.push(*terminal)),
+                Sym::Nonterminal(nonterminal) => {
+                    words = concat_k(&words, &first[nonterminal.index()], k)
+                        .into_iter()
+                        .collect()
                 }
             }
-            items.push((id, (terminals, symbols)));
+            words = add(position + 1, words);
         }
 
-        cache.insert(of, items);
+        words
     }
 
-    pub fn first_k(&self, k: usize) -> FirstTable {
-        let mut queue = VecDeque::new();
-        let mut cache = HashMap::new();
-        let mut table = FirstTable::new();
+    /// FIRST_k of a string of symbols given FIRST_k of the nonterminals
+    pub(crate) fn first_k_of(&self, k: usize, symbols: &[Sym], first: &[WordSet]) -> WordSet {
+        let mut words = WordSet::from_iter([Word::new()]);
+
+        for symbol in symbols {
+            words = match symbol {
+                Sym::Terminal(terminal) => words
+                    .into_iter()
+                    .map(|mut word| {
+                        if word.len() < k {
+                            word.push(*terminal);
+                        }
+                        word
+                    })
+                    .collect(),
+                Sym::Nonterminal(nonterminal) => concat_k(&words, &first[nonterminal.index()], k),
+            };
+        }
 
-        for (key, rule) in &self.productions {
-            let mut sets = FirstSets::new();
-            let mut items = Vec::new();
+        words
+    }
 
-            for (id, symbols) in rule {
-                sets.insert(*id, FirstSet::new());
-                items.push((*id, (Vec::new(), VecDeque::from_iter(symbols.clone()))));
+    /// Whether each nonterminal derives at least one terminal string
+    pub(crate) fn productive(&self) -> Vec<bool> {
+        let mut productive = vec![false; self.rules.len()];
+        let mut worklist = Worklist::invoked_first(self);
+
+        while let Some(of) = worklist.pop() {
+            if !productive[of.index()]
+                && self.rules[of.index()]
+                    .iter()
+                    .any(|(_, symbols)| all_productive(symbols, &productive))
+            {
+                productive[of.index()] = true;
+                self.dependents[of.index()]
+                    .iter()
+                    .for_each(|dependent| worklist.push(*dependent));
             }
-
-            table.insert(key.clone(), sets);
-            cache.insert(key.clone(), items);
-            queue.push_back(key.clone());
         }
 
-        while let Some(of) = queue.pop_front() {
-            self.first_k_of(k, of, &mut queue, &mut cache);
+        productive
+    }
+
+    /// FIRST_1 as bitsets, with an empty word for nullable productions.
+    /// Productions which derive no terminal string have an empty set, as for larger k
+    fn first_1(&self) -> Vec<Vec<(Id, WordSet)>> {
+        let productive = self.productive();
+        let mut nullable = vec![false; self.rules.len()];
+        let mut first = vec![TerminalSet::new(); self.rules.len()];
+
+        let mut worklist = Worklist::invoked_first(self);
+
+        while let Some(of) = worklist.pop() {
+            let mut changed = false;
+
+            for (_, symbols) in &self.rules[of.index()] {
+                if !all_productive(symbols, &productive) {
+                    continue;
+                }
+
+                let (set, empty) = self.first_1_of(symbols, &first, &nullable);
+                changed |= first[of.index()].union(&set);
+                if empty && !nullable[of.index()] {
+                    nullable[of.index()] = true;
+                    changed = true;
+                }
+            }
+
+            if changed {
+                self.dependents[of.index()]
+                    .iter()
+                    .for_each(|dependent| worklist.push(*dependent));
+            }
         }
 
-        for (key, items) in cache {
-            for (id, (terminals, _)) in items {
-                table.get_mut(&key).unwrap()[&id].insert(terminals);
+        self.rules
+            .iter()
+            .map(|rule| {
+                rule.iter()
+                    .map(|(id, symbols)| {
+                        if !all_productive(symbols, &productive) {
+                            return (*id, WordSet::default());
+                        }
+
+                        let (set, empty) = self.first_1_of(symbols, &first, &nullable);
+                        let mut words = set
+                            .iter()
+                            .map(|terminal| Word::from_slice(&[terminal]))
+                            .collect::<WordSet>();
+                        if empty {
+                            words.insert(Word::new());
+                        }
+                        (*id, words)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub(crate) fn first_1_of(
+        &self,
+        symbols: &[Sym],
+        first: &[TerminalSet],
+        nullable: &[bool],
+    ) -> (TerminalSet, bool) {
+        let mut set = TerminalSet::new();
+
+        for symbol in symbols {
+            match symbol {
+                Sym::Terminal(terminal) => {
+                    set.insert(*terminal);
+                    return (set, false);
+                }
+                Sym::Nonterminal(nonterminal) => {
+                    set.union(&first[nonterminal.index()]);
+                    if !nullable[nonterminal.index()] {
+                        return (set, false);
+                    }
+                }
             }
         }
 
+        (set, true)
+    }
+}
+
+impl Grammar {
+    pub fn first_k(&self, k: usize) -> FirstTable {
+        let interned = Interned::new(self);
+        interned.first_table(&interned.first_k(k))
+    }
+}
+
+impl Interned {
+    pub(crate) fn first_table(&self, first: &[Vec<(Id, WordSet)>]) -> FirstTable {
+        let mut table = FirstTable::new();
+
+        for (key, sets) in self.nonterminals.iter().zip(first) {
+            let sets = sets
+                .iter()
+                .map(|(id, words)| {
+                    let set = words.iter().map(|word| self.resolve(word)).collect();
+                    (*id, set)
+                })
+                .collect();
+            table.insert(key.clone(), sets);
+        }
+
         table
     }
 }
@@ -159,9 +374,12 @@ impl Grammar {
 #[cfg(test)]
 mod test {
 
+    use std::collections::{HashSet, VecDeque};
+
     use crate::{
         builder::Syntactical,
-        grammar::{Grammar, Id, Key, Rule, Symbol, Terminal},
+        grammar::{Grammar, Id, Key, Rule, Symbol, Terminal, Terminals},
+        test_util::random_grammar,
     };
 
     #[allow(dead_code)]
@@ -258,4 +476,109 @@ mod test {
         assert_eq!(end.len(), 1);
         assert!(end.contains(&Vec::new()));
     }
+
+    /// FIRST_k by enumerating leftmost derivations from `symbols`
+    /// through sentential forms of at most `bound` symbols
+    fn oracle(grammar: &Grammar, symbols: &[Symbol], k: usize, bound: usize) -> HashSet<Terminals> {
+        // Forms are strings of small ids, terminals as `Err` and nonterminals as `Ok`
+        let keys = grammar.keys().collect::<Vec<_>>();
+        let mut terminals = Vec::<Terminal>::new();
+        let mut encode = |symbols: &[Symbol]| {
+            symbols
+                .iter()
+                .filter_map(|symbol| match symbol {
+                    Symbol::Epsilon => None,
+                    Symbol::Nonterminal(nonterminal) => Some(Ok(keys
+                        .iter()
+                        .position(|key| *key == nonterminal.0)
+                        .unwrap())),
+                    Symbol::Terminal(terminal) => Some(Err(
+                        match terminals.iter().position(|other| other == terminal) {
+                            Some(index) => index,
+                            None => {
+                                terminals.push(terminal.clone());
+                                terminals.len() - 1
+                            }
+                        },
+                    )),
+                })
+                .collect::<Vec<Result<usize, usize>>>()
+        };
+
+        let rules = keys
+            .iter()
+            .map(|key| {
+                grammar
+                    .get(key)
+                    .unwrap()
+                    .values()
+                    .map(|symbols| encode(symbols))
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+        let start = encode(symbols);
+
+        let mut words = HashSet::new();
+        let mut visited = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(form) = queue.pop_front() {
+            let Some(position) = form.iter().position(Result::is_ok) else {
+                words.insert(
+                    form.iter()
+                        .take(k)
+                        .map(|symbol| terminals[symbol.unwrap_err()].clone())
+                        .collect(),
+                );
+                continue;
+            };
+
+            for symbols in &rules[form[position].unwrap()] {
+                let mut derived = form[..position].to_vec();
+                derived.extend_from_slice(symbols);
+                derived.extend_from_slice(&form[position + 1..]);
+
+                // once the prefix is k terminals long, later terminals can't show up in the word
+                if derived.iter().take_while(|symbol| symbol.is_err()).count() >= k {
+                    let mut count = 0;
+                    derived.retain(|symbol| {
+                        count += symbol.is_err() as usize;
+                        symbol.is_ok() || count <= k
+                    });
+                }
+
+                if derived.len() <= bound && visited.insert(derived.clone()) {
+                    queue.push_back(derived);
+                }
+            }
+        }
+
+        words
+    }
+
+    #[test]
+    fn first_oracle() {
+        for seed in 1..=100 {
+            let grammar = random_grammar(seed);
+            let first_tables = (1..=3).map(|k| grammar.first_k(k)).collect::<Vec<_>>();
+
+            for key in grammar.keys() {
+                for (id, symbols) in grammar.get(&key).unwrap() {
+                    let words = oracle(&grammar, symbols, 3, 10);
+
+                    for (k, first_table) in (1..=3).zip(&first_tables) {
+                        let truncated = words
+                            .iter()
+                            .map(|word| word.iter().take(k).cloned().collect())
+                            .collect::<HashSet<Terminals>>();
+
+                        assert_eq!(
+                            first_table[&key][id], truncated,
+                            "FIRST_{k} of {key}#{id} for seed {seed}:\n{grammar}"
+                        );
+                    }
+                }
+            }
+        }
+    }
 }
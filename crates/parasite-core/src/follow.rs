@@ -1,141 +1,198 @@
-use std::{
-    collections::{HashMap, HashSet, VecDeque},
-    hash::Hash,
-};
+use std::collections::{HashMap, HashSet};
 
-use super::first::FirstTable;
-use crate::grammar::{Grammar, Key, Symbol, Terminals};
+use super::first::{all_productive, FirstTable};
+use crate::{
+    grammar::{Grammar, Key, Terminals},
+    intern::{Interned, Sym, SymbolId, TerminalSet, Word, WordSet, Worklist},
+};
 
 pub type FollowSets = HashMap<Key, FollowSet>;
 pub type FollowSet = HashSet<Terminals>;
 
 impl Grammar {
     pub fn follow_k(&self, k: usize, first_table: &FirstTable) -> FollowSets {
-        let mut sets: HashMap<Key, Vec<FollowItem>> =
-            HashMap::from_iter(self.keys().map(|key| (key, Vec::new())));
+        self.follow_k_from(k, first_table, &self.start)
+    }
+
+    pub fn follow_k_from(&self, k: usize, first_table: &FirstTable, entry: &Key) -> FollowSets {
+        let interned = self.interned();
+        let first = interned.first_words(first_table);
+        let follow = interned.follow_k(k, &first, interned.nonterminal_id(entry));
+
+        interned
+            .nonterminals
+            .iter()
+            .zip(follow)
+            .map(|(key, set)| {
+                let set = set.iter().map(|word| interned.resolve(word)).collect();
+                (key.clone(), set)
+            })
+            .collect()
+    }
+}
+
+impl Interned {
+    /// FOLLOW_k of every nonterminal, indexed like `rules`
+    pub(crate) fn follow_k(&self, k: usize, first: &[WordSet], entry: SymbolId) -> Vec<WordSet> {
+        if k == 1 {
+            return self.follow_1(first, entry);
+        }
 
-        for (key, rule) in &self.productions {
+        // for every A -> α B β, FOLLOW_k(B) includes FIRST_k(β) ⊕k FOLLOW_k(A), the words of
+        // FIRST_k(β) which are k long only depend on FOLLOW_k(A) being nonempty
+        let mut invocations = vec![Vec::new(); self.rules.len()];
+
+        for (of, rule) in self.rules.iter().enumerate() {
             for (_, symbols) in rule {
-                let mut invocation = None;
-                let mut terminals = Vec::new();
-
-                for symbol in symbols {
-                    match symbol {
-                        Symbol::Epsilon => (),
-                        Symbol::Nonterminal(nonterminal) => {
-                            if let Some(invoked) = invocation.replace(nonterminal.0.clone()) {
-                                let mut terminals = std::mem::replace(&mut terminals, Vec::new());
-                                terminals.truncate(k);
-                                // sets.entry(invoked).and_modify(|entry| {
-                                //     entry.push(FollowItem::first(nonterminal.0.clone(), terminals))
-                                // });
-                                sets.get_mut(&invoked)
-                                    .unwrap()
-                                    .push(FollowItem::first(nonterminal.0.clone(), terminals))
-                            } else {
-                                terminals.clear();
-                            }
-                        }
-                        Symbol::Terminal(terminal) => terminals.push(terminal.clone()),
-                    }
+                for (position, symbol) in symbols.iter().enumerate() {
+                    let Sym::Nonterminal(invoked) = symbol else {
+                        continue;
+                    };
+
+                    let (complete, rest) = self
+                        .first_k_of(k, &symbols[position + 1..], first)
+                        .into_iter()
+                        .partition::<Vec<_>, _>(|word| word.len() >= k);
+                    invocations[of].push((*invoked, complete, rest));
                 }
+            }
+        }
 
-                if let Some(invoked) = invocation {
-                    if terminals.is_empty() && &invoked != key {
-                        // invoked is last element and not recursive therefore the follow set of it must be added
-                        // to the production symbol on the lhs of the production containing this symbol
-                        // A -> B
-                        // follow(A) += follow(B)
-                        sets.get_mut(&key)
-                            .unwrap()
-                            .push(FollowItem::follow(invoked, terminals));
-                    } else {
-                        terminals.truncate(k);
-                        sets.get_mut(&invoked)
-                            .unwrap()
-                            .push(FollowItem::new(terminals));
+        // the entry of the parse is always followed by the end of the input
+        let mut follow = vec![WordSet::default(); self.rules.len()];
+        follow[entry.index()].insert(Word::from_slice(&[self.eoi()]));
+
+        // only the words added since a nonterminal was last visited are passed on
+        let mut added = vec![Vec::new(); self.rules.len()];
+        added[entry.index()].push(Word::from_slice(&[self.eoi()]));
+        let mut worklist = Worklist::invoking_first(self);
+
+        while let Some(of) = worklist.pop() {
+            let words = std::mem::take(&mut added[of.index()]);
+            if words.is_empty() {
+                continue;
+            }
+            // whether these are the first words of FOLLOW_k(A)
+            let reached = words.len() == follow[of.index()].len();
+
+            for (invoked, complete, rest) in &invocations[of.index()] {
+                let complete = complete.iter().filter(|_| reached).cloned();
+                let concatenated = rest.iter().flat_map(|prefix| {
+                    words.iter().map(|suffix| {
+                        let mut word = prefix.clone();
+                        word.extend(suffix.iter().take(k - prefix.len()).copied());
+                        word
+                    })
+                });
+
+                for word in complete.chain(concatenated) {
+                    if follow[invoked.index()].insert(word.clone()) {
+                        added[invoked.index()].push(word);
+                        worklist.push(*invoked);
                     }
                 }
             }
         }
 
-        let mut queue = VecDeque::from_iter(self.keys());
+        follow
+    }
 
-        while let Some(key) = queue.pop_front() {
-            for (pos, item) in sets[&key].clone().into_iter().enumerate() {
-                match item.reference {
-                    Reference::Follow(invoked) => {
-                        let mut following = sets[&invoked].clone();
-                        sets.get_mut(&key).unwrap().append(&mut following);
-                        sets.get_mut(&key).unwrap().swap_remove(pos);
-                        queue.push_back(key.clone());
+    /// FOLLOW_1 as bitsets
+    fn follow_1(&self, first: &[WordSet], entry: SymbolId) -> Vec<WordSet> {
+        let mut productive = vec![false; self.rules.len()];
+        let mut nullable = vec![false; self.rules.len()];
+        let mut first_1 = vec![TerminalSet::new(); self.rules.len()];
+        for (of, words) in first.iter().enumerate() {
+            for word in words {
+                productive[of] = true;
+                match word.first() {
+                    Some(terminal) => {
+                        first_1[of].insert(*terminal);
                     }
-                    Reference::First(invoked) => {
-                        for (_, first_set) in &first_table[&invoked] {
-                            for first_item in first_set {
-                                let mut terminals = item.terminals.clone();
-                                terminals.append(&mut first_item.clone());
-                                sets.get_mut(&key).unwrap().push(FollowItem::new(terminals));
-                            }
-                        }
-                        sets.get_mut(&key).unwrap().swap_remove(pos);
-                    }
-                    Reference::None => (),
+                    None => nullable[of] = true,
                 }
             }
         }
 
-        sets.into_iter()
-            .map(|(key, set)| {
-                let set = set.into_iter().map(|item| item.terminals).collect();
-                (key, set)
-            })
-            .collect()
-    }
-}
+        // for every A -> α B β, FOLLOW_1(B) includes FIRST_1(β) once FOLLOW_1(A) is nonempty,
+        // and FOLLOW_1(A) if β is nullable
+        let mut invocations = vec![Vec::new(); self.rules.len()];
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct FollowItem {
-    reference: Reference,
-    terminals: Terminals,
-}
+        for (of, rule) in self.rules.iter().enumerate() {
+            for (_, symbols) in rule {
+                for (position, symbol) in symbols.iter().enumerate() {
+                    let Sym::Nonterminal(invoked) = symbol else {
+                        continue;
+                    };
+
+                    // β derives no terminal string, as FIRST_1(β) is empty for larger k
+                    let rest = &symbols[position + 1..];
+                    if !all_productive(rest, &productive) {
+                        continue;
+                    }
 
-impl FollowItem {
-    fn new(terminals: Terminals) -> Self {
-        Self {
-            reference: Reference::None,
-            terminals,
+                    let (set, empty) = self.first_1_of(rest, &first_1, &nullable);
+                    invocations[of].push((*invoked, set, empty));
+                }
+            }
         }
-    }
 
-    fn first(reference: Key, terminals: Terminals) -> Self {
-        Self {
-            reference: Reference::First(reference),
-            terminals,
-        }
-    }
+        let mut follow = vec![TerminalSet::new(); self.rules.len()];
+        follow[entry.index()].insert(self.eoi());
+
+        let mut worklist = Worklist::invoking_first(self);
+        while let Some(of) = worklist.pop() {
+            let following = follow[of.index()].clone();
+            if following.is_empty() {
+                continue;
+            }
 
-    fn follow(reference: Key, terminals: Terminals) -> Self {
-        Self {
-            reference: Reference::Follow(reference),
-            terminals,
+            for (invoked, set, empty) in &invocations[of.index()] {
+                let mut changed = follow[invoked.index()].union(set);
+                if *empty {
+                    changed |= follow[invoked.index()].union(&following);
+                }
+                if changed {
+                    worklist.push(*invoked);
+                }
+            }
         }
+
+        follow
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|terminal| Word::from_slice(&[terminal]))
+                    .collect()
+            })
+            .collect()
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum Reference {
-    Follow(Key),
-    First(Key),
-    None,
+    /// Interns the words of a FIRST table of the same grammar, joined over the productions
+    /// of each nonterminal
+    pub(crate) fn first_words(&self, first_table: &FirstTable) -> Vec<WordSet> {
+        self.nonterminals
+            .iter()
+            .map(|key| {
+                first_table[key]
+                    .values()
+                    .flatten()
+                    .map(|terminals| self.word(terminals))
+                    .collect()
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod test {
 
+    use std::collections::HashSet;
+
     use crate::{
         builder::Syntactical,
-        grammar::{Grammar, Id, Key, Rule, Symbol, Terminal},
+        grammar::{Grammar, Id, Key, Rule, Symbol, Terminal, Terminals},
+        test_util::grammar,
     };
 
     #[allow(dead_code)]
@@ -226,8 +283,12 @@ mod test {
         let follow_sets = grammar.follow_k(k, &first_table);
 
         let a = &follow_sets[&Key::of::<A>()];
-        assert_eq!(a.len(), 1);
-        assert!(a.contains(&vec![Terminal::from(Key::of::<u8>())]));
+        assert_eq!(a.len(), 2);
+        assert!(a.contains(&vec![Terminal::from(Key::of::<u8>()), Terminal::eoi()]));
+        assert!(a.contains(&vec![
+            Terminal::from(Key::of::<u8>()),
+            Terminal::from(Key::of::<bool>()),
+        ]));
     }
 
     #[test]
@@ -242,7 +303,72 @@ mod test {
         let follow_sets = grammar.follow_k(k, &first_table);
 
         let a = &follow_sets[&Key::of::<A>()];
-        assert_eq!(a.len(), 1);
-        assert!(a.contains(&vec![Terminal::from(Key::of::<u8>())]));
+        assert_eq!(a.len(), 2);
+        assert!(a.contains(&vec![Terminal::from(Key::of::<u8>()), Terminal::eoi()]));
+        assert!(a.contains(&vec![
+            Terminal::from(Key::of::<u8>()),
+            Terminal::from(Key::of::<bool>()),
+            Terminal::from(Key::of::<u8>()),
+        ]));
+    }
+
+    fn words(words: &[&[&str]]) -> HashSet<Terminals> {
+        words
+            .iter()
+            .map(|word| {
+                word.iter()
+                    .map(|name| match *name {
+                        "$" => Terminal::eoi(),
+                        name => Terminal::from(Key::new(name)),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn follow_nullable_1() {
+        // S -> A B c, A -> a, B -> ε
+        let grammar = grammar(&[("S", &[&["A", "B", "c"]]), ("A", &[&["a"]]), ("B", &[&[]])]);
+
+        let follow_sets = grammar.follow_k(1, &grammar.first_k(1));
+        assert_eq!(follow_sets[&Key::new("A")], words(&[&["c"]]));
+        assert_eq!(follow_sets[&Key::new("B")], words(&[&["c"]]));
+
+        let follow_sets = grammar.follow_k(2, &grammar.first_k(2));
+        assert_eq!(follow_sets[&Key::new("A")], words(&[&["c", "$"]]));
+        assert_eq!(follow_sets[&Key::new("B")], words(&[&["c", "$"]]));
+    }
+
+    #[test]
+    fn follow_nullable_2() {
+        // S -> A B C d | S e, A -> a, B -> b | ε, C -> ε | C c
+        let grammar = grammar(&[
+            ("S", &[&["A", "B", "C", "d"], &["S", "e"]]),
+            ("A", &[&["a"]]),
+            ("B", &[&["b"], &[]]),
+            ("C", &[&[], &["C", "c"]]),
+        ]);
+
+        let follow_sets = grammar.follow_k(2, &grammar.first_k(2));
+        assert_eq!(
+            follow_sets[&Key::new("A")],
+            words(&[
+                &["b", "c"],
+                &["b", "d"],
+                &["c", "c"],
+                &["c", "d"],
+                &["d", "e"],
+                &["d", "$"]
+            ])
+        );
+        assert_eq!(
+            follow_sets[&Key::new("C")],
+            words(&[&["c", "c"], &["c", "d"], &["d", "e"], &["d", "$"]])
+        );
+        assert_eq!(
+            follow_sets[&Key::new("S")],
+            words(&[&["e", "e"], &["e", "$"], &["$"]])
+        );
     }
 }
@@ -1,18 +1,91 @@
 use core::fmt;
 use std::{
-    any::type_name,
+    any::{type_name, TypeId},
+    cmp::Ordering,
     collections::HashMap,
     hash::Hash,
     ops::{Index, IndexMut},
 };
 
 use ecow::EcoString;
+
+use crate::{
+    builder::Syntactical,
+    intern::{Cache, Interned},
+    parser::ParseError,
+};
 use owo_colors::{OwoColorize, Style};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Grammar {
+    /// The rules of the nonterminals. The analyses intern them on first use, so change them
+    /// through [`Grammar::insert`] or [`Grammar::get_mut`] once the grammar was analysed
     pub productions: HashMap<Key, Rule>,
     pub start: Key,
+    /// How terminals are called in error messages
+    pub names: HashMap<Key, String>,
+    /// Labels summarising what nonterminals expect
+    pub expected: HashMap<Key, Expected>,
+    /// Names and origins of the productions
+    pub metadata: HashMap<(Key, Id), Production>,
+    interned: Cache,
+}
+
+/// What is known about a production besides its symbols
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Production {
+    /// Name of the production, e.g. `Branch::Leaf`
+    pub label: Option<String>,
+    /// Field names of the symbols, if they are named
+    pub fields: Vec<Option<String>>,
+    /// The Rust type or grammar rule the production was generated from
+    pub origin: Option<String>,
+    /// File and line the production is written at
+    pub location: Option<(String, u32)>,
+    /// Doc comments of the origin
+    pub docs: Vec<String>,
+}
+
+impl Production {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: Some(label.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_fields<S: Into<String>>(
+        mut self,
+        fields: impl IntoIterator<Item = Option<S>>,
+    ) -> Self {
+        self.fields = fields
+            .into_iter()
+            .map(|field| field.map(Into::into))
+            .collect();
+        self
+    }
+
+    pub fn with_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origin = Some(origin.into());
+        self
+    }
+
+    pub fn with_location(mut self, file: impl Into<String>, line: u32) -> Self {
+        self.location = Some((file.into(), line));
+        self
+    }
+
+    pub fn with_docs<S: Into<String>>(mut self, docs: impl IntoIterator<Item = S>) -> Self {
+        self.docs = docs.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// Labels used instead of listing the expected terminals, e.g. "expression"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expected {
+    pub rule: Option<String>,
+    pub productions: HashMap<Id, String>,
 }
 
 impl Grammar {
@@ -20,6 +93,10 @@ impl Grammar {
         Self {
             productions: HashMap::new(),
             start,
+            names: HashMap::new(),
+            expected: HashMap::new(),
+            metadata: HashMap::new(),
+            interned: Cache::default(),
         }
     }
 
@@ -27,12 +104,10 @@ impl Grammar {
         self.productions.len()
     }
 
-    pub fn insert(&mut self, key: Key, mut rule: Rule) -> Option<Rule> {
-        if &key == &self.start {
-            for (_, sym) in &mut rule {
-                sym.push(Symbol::terminal(Key::new("$")))
-            }
-        }
+    /// Inserts the rule as is, the end of the input is not appended to the start rule,
+    /// the parsers expect [`Terminal::eoi`] after the entry themselves
+    pub fn insert(&mut self, key: Key, rule: Rule) -> Option<Rule> {
+        self.interned.clear();
         self.productions.insert(key, rule)
     }
 
@@ -40,6 +115,23 @@ impl Grammar {
         self.productions.contains_key(key)
     }
 
+    /// Panics unless a table can be built for `entry`
+    #[track_caller]
+    pub(crate) fn assert_entry(&self, entry: &Key) {
+        assert!(
+            self.contains(entry),
+            "{entry} is no nonterminal of the grammar"
+        );
+    }
+
+    /// Fails unless the parse can start at `entry`
+    pub(crate) fn check_entry<E>(&self, entry: &Key) -> Result<(), ParseError<E>> {
+        match self.contains(entry) {
+            true => Ok(()),
+            false => Err(ParseError::UnknownEntry(entry.clone())),
+        }
+    }
+
     pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
         self.productions.keys().cloned()
     }
@@ -48,18 +140,119 @@ impl Grammar {
         self.productions.get(key)
     }
 
+    /// The grammar with dense ids, interned on first use
+    pub(crate) fn interned(&self) -> &Interned {
+        self.interned.get(self)
+    }
+
     pub fn get_mut(&mut self, key: &Key) -> Option<&mut Rule> {
+        self.interned.clear();
         self.productions.get_mut(key)
     }
 }
 
 impl Grammar {
-    pub fn get_by_type<T>(&self) -> Option<&Rule> {
+    /// Registers `T` as terminal together with its display name
+    pub fn terminal<T: Syntactical + ?Sized>(&mut self) -> Symbol {
+        let key = T::key();
+        self.names.insert(key.clone(), T::display_name());
+        Symbol::terminal(key)
+    }
+
+    pub fn display_name(&self, terminal: &Terminal) -> String {
+        if terminal.is_eoi() {
+            "end of input".to_string()
+        } else if let Some(name) = self.names.get(&terminal.0) {
+            name.clone()
+        } else {
+            format!("`{}`", terminal.0)
+        }
+    }
+
+    pub fn set_production(&mut self, key: Key, id: Id, production: Production) {
+        self.metadata.insert((key, id), production);
+    }
+
+    pub fn production(&self, key: &Key, id: Id) -> Option<&Production> {
+        self.metadata.get(&(key.clone(), id))
+    }
+
+    /// The label of the production, falling back to its key and id
+    pub fn production_name(&self, key: &Key, id: Id) -> String {
+        match self.production(key, id).and_then(|p| p.label.as_ref()) {
+            Some(label) => label.clone(),
+            None => format!("{key}#{id}"),
+        }
+    }
+
+    /// Displays the leftmost derivation `applied` one production per line
+    pub fn trace<'a>(&'a self, applied: &'a [(Key, Id)]) -> Trace<'a> {
+        Trace {
+            grammar: self,
+            applied,
+        }
+    }
+
+    /// Labels the whole rule of `key`, or only its production `id`
+    pub fn set_expected(&mut self, key: Key, id: Option<Id>, label: impl Into<String>) {
+        let expected = self.expected.entry(key).or_default();
+        match id {
+            Some(id) => expected.productions.insert(id, label.into()),
+            None => expected.rule.replace(label.into()),
+        };
+    }
+
+    /// The label summarising the `candidates` of `key`. Candidates all starting
+    /// with the same nonterminal are summarised by its label
+    pub fn expected_label(&self, key: &Key, candidates: &[Id]) -> Option<&str> {
+        let mut key = key;
+        let mut candidates = candidates.to_vec();
+        let mut visited = vec![];
+
+        while !visited.contains(&key) {
+            visited.push(key);
+
+            if let Some(expected) = self.expected.get(key) {
+                if let Some(label) = &expected.rule {
+                    return Some(label);
+                }
+
+                let mut labels = candidates.iter().map(|id| expected.productions.get(id));
+                if let Some(Some(label)) = labels.next() {
+                    if labels.all(|other| other == Some(label)) {
+                        return Some(label);
+                    }
+                }
+            }
+
+            let rule = self.productions.get(key)?;
+            let mut firsts = candidates.iter().map(|id| rule[id].first());
+            let first = match firsts.next() {
+                Some(Some(Symbol::Nonterminal(first))) => &first.0,
+                _ => return None,
+            };
+            let same = |other: Option<&Symbol>| {
+                other.and_then(Symbol::as_nonterminal).map(|other| &other.0) == Some(first)
+            };
+            if !firsts.all(same) {
+                return None;
+            }
+
+            key = first;
+            candidates = self.productions.get(key)?.keys().copied().collect();
+        }
+
+        None
+    }
+}
+
+impl Grammar {
+    pub fn get_by_type<T: ?Sized + 'static>(&self) -> Option<&Rule> {
         let key = Key::of::<T>();
         self.get(&key)
     }
 
-    pub fn get_mut_by_type<T>(&mut self) -> Option<&mut Rule> {
+    pub fn get_mut_by_type<T: ?Sized + 'static>(&mut self) -> Option<&mut Rule> {
         let key = Key::of::<T>();
         self.get_mut(&key)
     }
@@ -78,18 +271,20 @@ impl fmt::Display for Grammar {
 
             let mut rule_iter = rule.iter();
 
-            if let Some((_, rhs)) = rule_iter.next() {
+            if let Some((id, rhs)) = rule_iter.next() {
                 write!(f, "{} {}", key.style(key_style), ":=".bold())?;
                 for sym in rhs {
                     write!(f, " {sym}")?;
                 }
+                self.fmt_label(f, key, *id)?;
             }
 
-            for (_, rhs) in rule_iter {
+            for (id, rhs) in rule_iter {
                 write!(f, "\n\t{}", "|".bold())?;
                 for sym in rhs {
                     write!(f, " {sym}")?;
                 }
+                self.fmt_label(f, key, *id)?;
             }
             writeln!(f, "\n\t{}", ";".bold())?;
         }
@@ -98,6 +293,40 @@ impl fmt::Display for Grammar {
     }
 }
 
+impl Grammar {
+    fn fmt_label(&self, f: &mut fmt::Formatter<'_>, key: &Key, id: Id) -> fmt::Result {
+        match self.production(key, id).and_then(|p| p.label.as_ref()) {
+            Some(label) => write!(f, "  {}", format!("// {label}").dimmed()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A leftmost derivation displayed with the production names of its grammar
+pub struct Trace<'a> {
+    grammar: &'a Grammar,
+    applied: &'a [(Key, Id)],
+}
+
+impl fmt::Display for Trace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, id) in self.applied {
+            write!(
+                f,
+                "{} {}",
+                self.grammar.production_name(key, *id).italic(),
+                ":=".bold()
+            )?;
+            for sym in &self.grammar.productions[key][id] {
+                write!(f, " {sym}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Index<&Key> for Grammar {
     type Output = Rule;
 
@@ -108,6 +337,7 @@ impl Index<&Key> for Grammar {
 
 impl IndexMut<&Key> for Grammar {
     fn index_mut(&mut self, index: &Key) -> &mut Self::Output {
+        self.interned.clear();
         self.productions.get_mut(index).unwrap()
     }
 }
@@ -233,6 +463,34 @@ impl fmt::Display for Nonterminal {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Terminal(pub Key);
 
+/// The identity of the end of the input, so that no terminal of a grammar is taken for it
+struct EndOfInput;
+
+impl Terminal {
+    /// Marks the end of the input in lookahead strings, displayed as `$`
+    pub fn eoi() -> Self {
+        Self(Key::of::<EndOfInput>().with_name("$"))
+    }
+
+    pub fn is_eoi(&self) -> bool {
+        *self == Self::eoi()
+    }
+
+    /// Orders by name like [`Key::cmp_by_name`]
+    pub fn cmp_by_name(&self, other: &Self) -> Ordering {
+        self.0.cmp_by_name(&other.0)
+    }
+}
+
+/// Orders lookahead strings terminal by terminal with [`Terminal::cmp_by_name`]
+pub fn cmp_words_by_name(left: &[Terminal], right: &[Terminal]) -> Ordering {
+    left.iter()
+        .zip(right)
+        .map(|(left, right)| left.cmp_by_name(right))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| left.len().cmp(&right.len()))
+}
+
 impl From<Key> for Terminal {
     fn from(value: Key) -> Self {
         Self(value)
@@ -245,29 +503,119 @@ impl fmt::Display for Terminal {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Key(EcoString);
+/// Identifies a symbol by its type, or by its name if it has none. The name is only
+/// used for display, as type names are neither unique nor stable
+#[derive(Debug, Clone)]
+pub struct Key {
+    /// The type, or [`Named`] if the name is the identity
+    id: TypeId,
+    name: EcoString,
+}
+
+/// Marks keys identified by their name
+struct Named;
 
 impl Key {
+    /// A key identified by `key` itself, distinct from all keys of types
     pub fn new(key: impl Into<EcoString>) -> Self {
-        Self(key.into())
+        let name = key.into();
+
+        Self {
+            id: TypeId::of::<Named>(),
+            name,
+        }
+    }
+
+    /// The key of `T`, displayed as its type name without module paths
+    pub fn of<T: ?Sized + 'static>() -> Self {
+        Self {
+            id: TypeId::of::<T>(),
+            name: short_name(type_name::<T>()).into(),
+        }
     }
 
-    pub fn of<T: ?Sized>() -> Self {
-        let type_name = type_name::<T>();
+    /// Replaces the display name, the identity of type keys stays the same
+    pub fn with_name(mut self, name: impl Into<EcoString>) -> Self {
+        self.name = name.into();
+        self
+    }
 
-        Key(EcoString::from(type_name))
+    fn is_named(&self) -> bool {
+        self.id == TypeId::of::<Named>()
     }
 
     pub fn as_str(&self) -> &str {
-        self.0.as_str()
+        self.name.as_str()
+    }
+
+    /// Orders by name and only then by identity, for everything shown to users,
+    /// as the order of type keys depends on the compiler
+    pub fn cmp_by_name(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name).then_with(|| self.cmp(other))
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && (!self.is_named() || self.name == other.name)
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Keys are ordered by their identity like they are compared, see [`Key::cmp_by_name`]
+/// for an order which does not depend on the compiler
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id).then_with(|| match self.is_named() {
+            true => self.name.cmp(&other.name),
+            false => Ordering::Equal,
+        })
+    }
+}
+
+impl Hash for Key {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        if self.is_named() {
+            self.name.hash(state);
+        }
     }
 }
 
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.name.fmt(f)
+    }
+}
+
+/// Strips the module paths of all type names in `name`
+fn short_name(name: &str) -> String {
+    let mut short = String::new();
+    let mut path_start = 0;
+    let mut rest = name;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(stripped) = rest.strip_prefix("::") {
+            short.truncate(path_start);
+            rest = stripped;
+            continue;
+        }
+
+        short.push(c);
+        if !(c.is_alphanumeric() || c == '_') {
+            path_start = short.len();
+        }
+        rest = &rest[c.len_utf8()..];
     }
+
+    short
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -284,3 +632,46 @@ impl fmt::Display for Id {
         self.0.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use std::{cmp::Ordering, collections::HashSet};
+
+    use crate::combinators::Just;
+
+    use super::{Key, Terminal};
+
+    #[test]
+    fn key_1() {
+        let key = Key::of::<Vec<Just<'a'>>>();
+        assert_eq!(key.to_string(), "Vec<Just<'a'>>");
+
+        let renamed = key.clone().with_name("letters");
+        assert_eq!(renamed, key);
+        assert_eq!(renamed.to_string(), "letters");
+
+        // names never collide with types
+        let keys = HashSet::from([Key::of::<u8>(), Key::new("u8"), Key::of::<u8>()]);
+        assert_eq!(keys.len(), 2);
+
+        // keys are ordered like they are compared, whatever their names
+        assert_eq!(renamed.cmp(&key), Ordering::Equal);
+        let a = Key::of::<u8>().with_name("a");
+        let b = Key::new("b");
+        assert_eq!(a.cmp(&b), Key::of::<u8>().cmp(&b));
+
+        // but shown by their names, not by the type ids of the compiler
+        let mut keys = vec![Key::of::<u8>(), Key::new("char"), Key::of::<bool>()];
+        keys.sort_by(Key::cmp_by_name);
+        assert_eq!(keys, [Key::of::<bool>(), Key::new("char"), Key::of::<u8>()]);
+
+        // a terminal of the grammar called `$` is no end of input
+        assert!(Terminal::eoi().is_eoi());
+        assert!(!Terminal::from(Key::new("$")).is_eoi());
+        assert_eq!(
+            Terminal::eoi().to_string(),
+            Terminal::from(Key::new("$")).to_string()
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use std::{
+    cmp::Reverse,
+    convert::Infallible,
+    ops::{Range, RangeInclusive},
+};
+
+use crate::{
+    grammar::{Grammar, Terminal},
+    parser::{Lookahead, ParseError},
+    span::{Span, Token},
+    table::Table,
+    tree::{span_of, Node, Tree},
+};
+
+/// Replaces the tokens in the byte `range` of the previous source by `replacement`.
+/// The spans of `replacement` are positions in the edited source,
+/// the new text is taken to end with the last token of `replacement`,
+/// the tokens after the edit keep their distance to the end of the edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: Vec<Token>,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, replacement: Vec<Token>) -> Self {
+        Self { range, replacement }
+    }
+
+    /// Applies the edit to the tokens of the previous source
+    ///
+    /// # Panics
+    /// If the range is reversed or only covers a part of a token
+    pub fn apply(&self, tokens: &[Token]) -> Vec<Token> {
+        self.check_range();
+        let start = tokens.partition_point(|token| token.span.end <= self.range.start);
+        let end =
+            start + tokens[start..].partition_point(|token| token.span.start < self.range.end);
+        self.check_removed(&tokens[start..end]);
+
+        let shift = self.shift();
+        let mut edited = tokens[..start].to_vec();
+        edited.extend(self.replacement.iter().cloned());
+        edited.extend(tokens[end..].iter().map(|token| moved(token, shift)));
+        edited
+    }
+
+    fn check_range(&self) {
+        assert!(
+            self.range.start <= self.range.end,
+            "the edit range {:?} is reversed",
+            self.range
+        );
+    }
+
+    /// Asserts that the tokens overlapping the range are replaced as a whole
+    fn check_removed(&self, removed: &[Token]) {
+        for token in removed.first().into_iter().chain(removed.last()) {
+            assert!(
+                self.range.start <= token.span.start && token.span.end <= self.range.end,
+                "the edit range {:?} only covers a part of the token at {}",
+                self.range,
+                token.span
+            );
+        }
+    }
+
+    /// How far the tokens after the edit move
+    fn shift(&self) -> isize {
+        let end = self
+            .replacement
+            .last()
+            .map_or(self.range.start, |token| token.span.end);
+        end as isize - self.range.end as isize
+    }
+}
+
+impl Grammar {
+    /// Applies `edit` to the input of `tree` and only reparses the smallest subtree,
+    /// whose lookahead and the lookahead of everything around it is not affected by the edit.
+    /// The subtree is replaced in place, the other subtrees are kept and only the ones
+    /// after the edit are moved. Returns the path of child indices to the reparsed subtree,
+    /// on errors `tree` is left as it was.
+    /// `table` must be built with the same `k` for the root of `tree`, see `Grammar::table_from`.
+    ///
+    /// # Panics
+    /// If the range of `edit` is reversed or only covers a part of a token
+    pub fn reparse(
+        &self,
+        table: &Table,
+        k: usize,
+        tree: &mut Tree,
+        edit: &Edit,
+    ) -> Result<Vec<usize>, ParseError> {
+        edit.check_range();
+        let mut after = Tokens::new(tree, edit.range.start).peekable();
+        let mut removed = Vec::new();
+        while let Some(token) = after.next_if(|token| token.span.start < edit.range.end) {
+            removed.push(token.clone());
+        }
+        edit.check_removed(&removed);
+
+        // a production was chosen with a lookahead that sees the edit, if it starts at most
+        // k - 1 tokens before the edit and at the latest at the last removed token,
+        // or at the token the replacement is inserted before
+        let next = after.peek().map_or(tree.span.end, |token| token.span.start);
+        let first = removed.first().map_or(next, |token| token.span.start);
+        let last = removed.last().map_or(next, |token| token.span.start);
+        let mut before = Vec::new();
+        let n = k.saturating_sub(1);
+        tokens_before(tree, edit.range.start, n, &mut before);
+        let window = match before.last() {
+            Some(token) if before.len() == n => token.span.start..=last,
+            Some(_) => 0..=last,
+            None => first..=last,
+        };
+
+        let mut candidates = Vec::new();
+        enclosing(tree, &edit.range, &mut Vec::new(), &mut candidates);
+        candidates.sort_by_key(|path| Reverse(path.len()));
+
+        for path in candidates {
+            if !path.is_empty() && outside_affected(tree, &path, &window) {
+                continue;
+            }
+
+            match self.reparse_subtree(table, k, tree, &path, edit) {
+                Ok(subtree) => {
+                    replace(tree, &path, subtree, edit.shift());
+                    return Ok(path);
+                }
+                Err(error) if path.is_empty() => return Err(error),
+                Err(_) => (),
+            }
+        }
+
+        unreachable!("the root encloses every edit")
+    }
+
+    /// Parses the edited tokens of the subtree at `path`,
+    /// followed by the moved tokens after it for the lookahead
+    fn reparse_subtree(
+        &self,
+        table: &Table,
+        k: usize,
+        tree: &Tree,
+        path: &[usize],
+        edit: &Edit,
+    ) -> Result<Tree, ParseError> {
+        let subtree = subtree_at(tree, path);
+        let tokens = edit.apply(&subtree.tokens());
+        let shift = edit.shift();
+        let following =
+            || Tokens::new(tree, subtree.span.end).map(move |token| moved(token, shift));
+
+        let mut input = Lookahead::new(
+            tokens
+                .iter()
+                .cloned()
+                .chain(following())
+                .map(Ok::<_, Infallible>),
+        );
+        let applied = self.parse_with(table, k, &subtree.key, &mut input, false)?;
+
+        if input.consumed != tokens.len() {
+            // only the root is reported, where the rest of the input is left over
+            let token = input.peek(1)?.remove(0);
+            return Err(ParseError::Unexpected {
+                terminal: token.terminal,
+                expected: vec![vec![Terminal::eoi()]],
+                label: None,
+                span: token.span,
+                open: Vec::new(),
+            });
+        }
+
+        // empty trees are placed before the next token, or after the previous one
+        let mut previous = Vec::new();
+        tokens_before(tree, subtree.span.start, 1, &mut previous);
+        let end = previous.first().map_or(0, |token| token.span.end);
+        let tokens = tokens
+            .into_iter()
+            .chain(following().next())
+            .collect::<Vec<_>>();
+
+        Ok(self.tree_after(&applied, &tokens, end))
+    }
+}
+
+/// The tokens of a tree which end after `pos` in order,
+/// the subtrees before `pos` are skipped without visiting them
+struct Tokens<'a> {
+    pos: usize,
+    stack: Vec<std::slice::Iter<'a, Node>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(tree: &'a Tree, pos: usize) -> Self {
+        let mut tokens = Self {
+            pos,
+            stack: Vec::new(),
+        };
+        tokens.descend(tree);
+        tokens
+    }
+
+    fn descend(&mut self, tree: &'a Tree) {
+        let skipped = tree
+            .children
+            .partition_point(|child| child.span().end <= self.pos);
+        self.stack.push(tree.children[skipped..].iter());
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(Node::Token(token)) => return Some(token),
+                Some(Node::Tree(tree)) => self.descend(tree),
+            }
+        }
+    }
+}
+
+/// Pushes the last `n` tokens of `tree` which start before `pos`, the closest first
+fn tokens_before<'a>(tree: &'a Tree, pos: usize, n: usize, found: &mut Vec<&'a Token>) {
+    let end = tree
+        .children
+        .partition_point(|child| child.span().start < pos);
+
+    for child in tree.children[..end].iter().rev() {
+        if found.len() == n {
+            return;
+        }
+        match child {
+            Node::Tree(child) => tokens_before(child, pos, n, found),
+            Node::Token(token) => found.push(token),
+        }
+    }
+}
+
+/// Collects the paths to the subtrees whose span contains `range`, children before their parents
+fn enclosing(
+    tree: &Tree,
+    range: &Range<usize>,
+    path: &mut Vec<usize>,
+    found: &mut Vec<Vec<usize>>,
+) {
+    for (i, child) in tree.children.iter().enumerate() {
+        match child {
+            Node::Tree(child) if child.span.start <= range.start && range.end <= child.span.end => {
+                path.push(i);
+                enclosing(child, range, path, found);
+                path.pop();
+            }
+            _ => (),
+        }
+    }
+
+    found.push(path.clone());
+}
+
+/// Whether a subtree outside of the one at `path` starts in `window`
+fn outside_affected(tree: &Tree, path: &[usize], window: &RangeInclusive<usize>) -> bool {
+    let mut node = tree;
+
+    for &i in path {
+        if window.contains(&node.span.start) {
+            return true;
+        }
+
+        let siblings = node.children.iter().enumerate().filter(|(j, _)| *j != i);
+        for (_, sibling) in siblings {
+            match sibling {
+                Node::Tree(sibling) if starts_within(sibling, window) => return true,
+                _ => (),
+            }
+        }
+
+        node = match &node.children[i] {
+            Node::Tree(child) => child,
+            Node::Token(_) => unreachable!(),
+        };
+    }
+
+    false
+}
+
+/// Whether `tree` or one of its subtrees starts in `window`
+fn starts_within(tree: &Tree, window: &RangeInclusive<usize>) -> bool {
+    if window.contains(&tree.span.start) {
+        return true;
+    }
+
+    // subtrees start between the start and the end of their parent
+    tree.span.start <= *window.end()
+        && *window.start() <= tree.span.end
+        && tree.children.iter().any(|child| match child {
+            Node::Tree(child) => starts_within(child, window),
+            Node::Token(_) => false,
+        })
+}
+
+fn subtree_at<'a>(tree: &'a Tree, path: &[usize]) -> &'a Tree {
+    path.iter().fold(tree, |node, &i| match &node.children[i] {
+        Node::Tree(child) => child,
+        Node::Token(_) => unreachable!(),
+    })
+}
+
+/// Puts `subtree` at `path` and moves everything after it by `shift`
+fn replace(tree: &mut Tree, path: &[usize], subtree: Tree, shift: isize) {
+    let Some((&i, path)) = path.split_first() else {
+        *tree = subtree;
+        return;
+    };
+
+    for sibling in &mut tree.children[i + 1..] {
+        move_node(sibling, shift);
+    }
+    match &mut tree.children[i] {
+        Node::Tree(child) => replace(child, path, subtree, shift),
+        Node::Token(_) => unreachable!(),
+    }
+
+    tree.span = span_of(&tree.children, tree.span.start);
+}
+
+fn move_node(node: &mut Node, shift: isize) {
+    match node {
+        Node::Tree(tree) => {
+            tree.span = move_span(tree.span, shift);
+            for child in &mut tree.children {
+                move_node(child, shift);
+            }
+        }
+        Node::Token(token) => token.span = move_span(token.span, shift),
+    }
+}
+
+fn moved(token: &Token, shift: isize) -> Token {
+    Token::new(token.terminal.clone(), move_span(token.span, shift))
+}
+
+fn move_span(span: Span, shift: isize) -> Span {
+    Span::new(
+        (span.start as isize + shift) as usize,
+        (span.end as isize + shift) as usize,
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Key, Terminal},
+        span::{Span, Token},
+    };
+
+    use super::{subtree_at, Edit};
+
+    type Groups = Vec<(bool, Vec<u8>, bool)>;
+
+    fn groups() -> Grammar {
+        let mut grammar = Grammar::new(Key::of::<Groups>());
+        let mut stack = Vec::new();
+
+        Groups::generate(&mut grammar, &mut stack);
+        grammar
+    }
+
+    #[test]
+    fn reparse_1() {
+        let grammar = groups();
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let k = 1;
+        let table = grammar.table(k);
+
+        let input = vec![
+            boolean.clone(),
+            uint.clone(),
+            uint.clone(),
+            boolean.clone(),
+            boolean.clone(),
+            boolean,
+        ];
+        let tokens = Token::indexed(&input).collect::<Vec<_>>();
+        let mut tree = grammar.tree(&grammar.parse(k, &input).unwrap(), &tokens);
+
+        let edit = Edit::new(3..3, vec![Token::new(uint, 3..4)]);
+        let edited = edit.apply(&tokens);
+        assert_eq!(edited[4].span, Span::new(4, 5));
+
+        let path = grammar.reparse(&table, k, &mut tree, &edit).unwrap();
+        assert_eq!(path, vec![0, 1, 1, 1]);
+
+        let terminals = edited
+            .iter()
+            .map(|t| t.terminal.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tree,
+            grammar.tree(&grammar.parse(k, &terminals).unwrap(), &edited)
+        );
+    }
+
+    #[test]
+    fn reparse_2() {
+        let grammar = groups();
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let k = 1;
+        let table = grammar.table(k);
+
+        let input = vec![
+            boolean.clone(),
+            uint.clone(),
+            boolean.clone(),
+            boolean.clone(),
+            boolean.clone(),
+        ];
+        let tokens = Token::indexed(&input).collect::<Vec<_>>();
+        let mut tree = grammar.tree(&grammar.parse(k, &input).unwrap(), &tokens);
+        let previous = tree.clone();
+
+        let edit = Edit::new(0..1, vec![Token::new(uint.clone(), 0..1)]);
+        assert!(grammar.reparse(&table, k, &mut tree, &edit).is_err());
+        assert_eq!(tree, previous);
+
+        let edit = Edit::new(
+            3..5,
+            vec![
+                Token::new(boolean.clone(), 3..4),
+                Token::new(uint, 4..5),
+                Token::new(boolean, 5..6),
+            ],
+        );
+        let edited = edit.apply(&tokens);
+        let terminals = edited
+            .iter()
+            .map(|t| t.terminal.clone())
+            .collect::<Vec<_>>();
+
+        grammar.reparse(&table, k, &mut tree, &edit).unwrap();
+        assert_eq!(
+            tree,
+            grammar.tree(&grammar.parse(k, &terminals).unwrap(), &edited)
+        );
+    }
+
+    #[test]
+    fn reparse_reuse() {
+        let grammar = groups();
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let k = 1;
+        let table = grammar.table(k);
+
+        let group = vec![boolean.clone(), uint.clone(), boolean];
+        let input = [group.clone(), group.clone(), group].concat();
+        let tokens = Token::indexed(&input).collect::<Vec<_>>();
+        let mut tree = grammar.tree(&grammar.parse(k, &input).unwrap(), &tokens);
+
+        // the first and the last group around the edited one
+        let first = subtree_at(&tree, &[0]).clone();
+        let first_children = subtree_at(&tree, &[0]).children.as_ptr();
+        let last_children = subtree_at(&tree, &[1, 1, 0]).children.as_ptr();
+
+        let edit = Edit::new(5..5, vec![Token::new(uint, 5..6)]);
+        let path = grammar.reparse(&table, k, &mut tree, &edit).unwrap();
+        assert_eq!(path, vec![1, 0, 1, 1]);
+
+        // the groups are neither parsed again nor copied
+        assert_eq!(subtree_at(&tree, &[0]), &first);
+        assert_eq!(subtree_at(&tree, &[0]).children.as_ptr(), first_children);
+        let last = subtree_at(&tree, &[1, 1, 0]);
+        assert_eq!(last.children.as_ptr(), last_children);
+        assert_eq!(last.span, Span::new(7, 10));
+
+        let edited = edit.apply(&tokens);
+        let terminals = edited
+            .iter()
+            .map(|t| t.terminal.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tree,
+            grammar.tree(&grammar.parse(k, &terminals).unwrap(), &edited)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "only covers a part of the token")]
+    fn edit_splits_token() {
+        let tokens = vec![Token::new(Terminal(Key::of::<u8>()), 0..2)];
+        Edit::new(1..3, Vec::new()).apply(&tokens);
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::OnceLock,
+};
+
+use rustc_hash::FxBuildHasher;
+use smallvec::SmallVec;
+
+use crate::grammar::{Grammar, Id, Key, Symbol, Terminal, Terminals};
+
+/// Dense id of an interned terminal or nonterminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct SymbolId(pub(crate) u32);
+
+impl SymbolId {
+    pub(crate) fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A lookahead string of interned terminals
+pub(crate) type Word = SmallVec<[SymbolId; 4]>;
+
+/// A set of words, which are short strings of small integers and so hashed with FxHash
+pub(crate) type WordSet = HashSet<Word, FxBuildHasher>;
+
+/// A production symbol with interned ids, epsilons are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Sym {
+    Terminal(SymbolId),
+    Nonterminal(SymbolId),
+}
+
+/// The grammar with terminals and nonterminals numbered from zero each,
+/// so that sets over them can be stored as bitsets and vectors
+#[derive(Debug, Clone)]
+pub(crate) struct Interned {
+    pub(crate) terminals: Vec<Terminal>,
+    terminal_ids: HashMap<Terminal, SymbolId>,
+    pub(crate) nonterminals: Vec<Key>,
+    nonterminal_ids: HashMap<Key, SymbolId>,
+    /// The productions of each nonterminal
+    pub(crate) rules: Vec<Vec<(Id, Vec<Sym>)>>,
+    /// The nonterminals invoking each nonterminal in one of their productions
+    pub(crate) dependents: Vec<Vec<SymbolId>>,
+    /// The nonterminals in postorder of their invocations, so that those which are invoked
+    /// come first, unless they are part of a cycle
+    pub(crate) postorder: Vec<SymbolId>,
+}
+
+impl Interned {
+    pub(crate) fn new(grammar: &Grammar) -> Self {
+        let mut interned = Self {
+            terminals: Vec::new(),
+            terminal_ids: HashMap::new(),
+            nonterminals: Vec::new(),
+            nonterminal_ids: HashMap::new(),
+            rules: Vec::new(),
+            dependents: Vec::new(),
+            postorder: Vec::new(),
+        };
+
+        interned.terminal(&Terminal::eoi());
+        let mut keys = grammar.keys().collect::<Vec<_>>();
+        keys.sort_by(Key::cmp_by_name);
+        for key in &keys {
+            interned.nonterminal(key);
+        }
+
+        for key in &keys {
+            let mut rule = grammar.productions[key]
+                .iter()
+                .map(|(id, symbols)| {
+                    let symbols = symbols
+                        .iter()
+                        .filter_map(|symbol| match symbol {
+                            Symbol::Epsilon => None,
+                            Symbol::Terminal(terminal) => {
+                                Some(Sym::Terminal(interned.terminal(terminal)))
+                            }
+                            Symbol::Nonterminal(nonterminal) => {
+                                Some(Sym::Nonterminal(interned.nonterminal(&nonterminal.0)))
+                            }
+                        })
+                        .collect();
+                    (*id, symbols)
+                })
+                .collect::<Vec<_>>();
+            rule.sort_by_key(|(id, _)| *id);
+            interned.rules.push(rule);
+        }
+
+        interned.dependents = vec![Vec::new(); interned.rules.len()];
+        for (of, rule) in interned.rules.iter().enumerate() {
+            for (_, symbols) in rule {
+                for symbol in symbols {
+                    if let Sym::Nonterminal(invoked) = symbol {
+                        let dependents = &mut interned.dependents[invoked.index()];
+                        if dependents.last() != Some(&SymbolId(of as u32)) {
+                            dependents.push(SymbolId(of as u32));
+                        }
+                    }
+                }
+            }
+        }
+
+        interned.postorder = interned.invocation_postorder();
+        interned
+    }
+
+    fn invocation_postorder(&self) -> Vec<SymbolId> {
+        let invoked = |of: usize| {
+            self.rules[of]
+                .iter()
+                .flat_map(|(_, symbols)| symbols)
+                .filter_map(|symbol| match symbol {
+                    Sym::Nonterminal(invoked) => Some(invoked.index()),
+                    Sym::Terminal(_) => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut postorder = Vec::with_capacity(self.rules.len());
+        let mut visited = vec![false; self.rules.len()];
+
+        for root in 0..self.rules.len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+
+            let mut stack = vec![(root, invoked(root))];
+            while let Some((of, pending)) = stack.last_mut() {
+                match pending.pop() {
+                    Some(next) if !visited[next] => {
+                        visited[next] = true;
+                        stack.push((next, invoked(next)));
+                    }
+                    Some(_) => (),
+                    None => {
+                        postorder.push(SymbolId(*of as u32));
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        postorder
+    }
+
+    fn terminal(&mut self, terminal: &Terminal) -> SymbolId {
+        if let Some(id) = self.terminal_ids.get(terminal) {
+            return *id;
+        }
+
+        let id = SymbolId(self.terminals.len() as u32);
+        self.terminals.push(terminal.clone());
+        self.terminal_ids.insert(terminal.clone(), id);
+        id
+    }
+
+    fn nonterminal(&mut self, key: &Key) -> SymbolId {
+        if let Some(id) = self.nonterminal_ids.get(key) {
+            return *id;
+        }
+
+        let id = SymbolId(self.nonterminals.len() as u32);
+        self.nonterminals.push(key.clone());
+        self.nonterminal_ids.insert(key.clone(), id);
+        id
+    }
+
+    pub(crate) fn nonterminal_id(&self, key: &Key) -> SymbolId {
+        self.nonterminal_ids[key]
+    }
+
+    /// The id of a terminal, if the grammar uses it
+    pub(crate) fn terminal_id(&self, terminal: &Terminal) -> Option<SymbolId> {
+        self.terminal_ids.get(terminal).copied()
+    }
+
+    pub(crate) fn eoi(&self) -> SymbolId {
+        self.terminal_ids[&Terminal::eoi()]
+    }
+
+    /// Interns a word over terminals of the grammar
+    pub(crate) fn word(&self, terminals: &[Terminal]) -> Word {
+        terminals
+            .iter()
+            .map(|terminal| self.terminal_ids[terminal])
+            .collect()
+    }
+
+    pub(crate) fn symbol(&self, symbol: Sym) -> Symbol {
+        match symbol {
+            Sym::Terminal(id) => Symbol::Terminal(self.terminals[id.index()].clone()),
+            Sym::Nonterminal(id) => Symbol::nonterminal(self.nonterminals[id.index()].clone()),
+        }
+    }
+
+    pub(crate) fn resolve(&self, word: &[SymbolId]) -> Terminals {
+        word.iter()
+            .map(|id| self.terminals[id.index()].clone())
+            .collect()
+    }
+}
+
+/// The interned form of a grammar, computed once and dropped whenever its rules change
+#[derive(Default)]
+pub(crate) struct Cache(OnceLock<Interned>);
+
+impl Cache {
+    pub(crate) fn get(&self, grammar: &Grammar) -> &Interned {
+        self.0.get_or_init(|| Interned::new(grammar))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.take();
+    }
+}
+
+/// A clone starts without the interned grammar
+impl Clone for Cache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+/// Grammars are equal by their rules, whether they are interned or not
+impl PartialEq for Cache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Cache {}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cache")
+    }
+}
+
+/// The nonterminals whose sets have to be computed again, each queued at most once
+pub(crate) struct Worklist {
+    queue: VecDeque<SymbolId>,
+    queued: Vec<bool>,
+}
+
+impl Worklist {
+    /// All nonterminals, the invoked ones before those invoking them, for sets which are
+    /// passed on to the invoking nonterminals
+    pub(crate) fn invoked_first(interned: &Interned) -> Self {
+        Self::new(interned.postorder.iter().copied())
+    }
+
+    /// All nonterminals, the invoking ones before those they invoke
+    pub(crate) fn invoking_first(interned: &Interned) -> Self {
+        Self::new(interned.postorder.iter().rev().copied())
+    }
+
+    pub(crate) fn empty(len: usize) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            queued: vec![false; len],
+        }
+    }
+
+    fn new(order: impl ExactSizeIterator<Item = SymbolId>) -> Self {
+        Self {
+            queued: vec![true; order.len()],
+            queue: order.collect(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, nonterminal: SymbolId) {
+        if !self.queued[nonterminal.index()] {
+            self.queued[nonterminal.index()] = true;
+            self.queue.push_back(nonterminal);
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<SymbolId> {
+        let nonterminal = self.queue.pop_front()?;
+        self.queued[nonterminal.index()] = false;
+        Some(nonterminal)
+    }
+}
+
+/// A set of interned terminals
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct TerminalSet(SmallVec<[u64; 2]>);
+
+impl TerminalSet {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the terminal was not yet contained
+    pub(crate) fn insert(&mut self, terminal: SymbolId) -> bool {
+        let (word, bit) = (terminal.index() / 64, terminal.index() % 64);
+        if self.0.len() <= word {
+            self.0.resize(word + 1, 0);
+        }
+
+        let added = self.0[word] & (1 << bit) == 0;
+        self.0[word] |= 1 << bit;
+        added
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    /// Returns whether any terminal was added
+    pub(crate) fn union(&mut self, other: &Self) -> bool {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+
+        let mut changed = false;
+        for (word, other) in self.0.iter_mut().zip(&other.0) {
+            changed |= *other & !*word != 0;
+            *word |= other;
+        }
+        changed
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = SymbolId> + '_ {
+        self.0.iter().enumerate().flat_map(|(index, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| SymbolId((index * 64 + bit) as u32))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{SymbolId, TerminalSet};
+
+    #[test]
+    fn terminal_set_1() {
+        let mut set = TerminalSet::new();
+        assert!(set.insert(SymbolId(3)));
+        assert!(set.insert(SymbolId(70)));
+        assert!(!set.insert(SymbolId(3)));
+
+        let mut other = TerminalSet::new();
+        other.insert(SymbolId(1));
+        assert!(set.union(&other));
+        assert!(!set.union(&other));
+
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec![SymbolId(1), SymbolId(3), SymbolId(70)]
+        );
+    }
+}
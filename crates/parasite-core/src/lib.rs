@@ -1,10 +1,31 @@
 #![feature(let_chains)]
 #![feature(associated_type_defaults)]
 
+pub mod ambiguity;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod baseline;
 pub mod builder;
 pub mod combinators;
+pub mod conflict;
+pub mod coverage;
+pub mod diagnostic;
+pub mod earley;
 pub mod first;
 pub mod follow;
 pub mod grammar;
+pub mod incremental;
+mod intern;
+pub mod lr;
 pub mod parser;
+pub mod pretty;
+pub mod push;
+pub mod sentence;
+pub mod span;
 pub mod table;
+#[cfg(any(test, feature = "bench"))]
+#[doc(hidden)]
+pub mod test_util;
+pub mod tree;
+pub mod unparse;
+pub mod visit;
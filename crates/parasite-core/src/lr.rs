@@ -0,0 +1,736 @@
+use core::fmt;
+use std::collections::{BTreeSet, HashMap};
+
+use owo_colors::OwoColorize;
+
+use crate::{
+    grammar::{cmp_words_by_name, Grammar, Id, Key, Terminal},
+    intern::{Interned, Sym, SymbolId, TerminalSet},
+    parser::{Lookahead, ParseError},
+    span::Token,
+};
+
+/// What an LR parser does in a state for the next terminal
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Consume the terminal and go to the state
+    Shift(usize),
+    /// Replace the symbols of the production on top of the stack by its nonterminal
+    Reduce(Key, Id),
+    /// The entry was parsed and the input ended
+    Accept,
+}
+
+/// Two actions for the same state and terminal. The table keeps the shift or
+/// the reduction by the first production, like yacc does. The productions of a
+/// nonterminal are in the order of their ids, the nonterminals in the order they
+/// are reached depth first from the entry, and accepting reduces `S' -> entry`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Conflict {
+    ShiftReduce {
+        state: usize,
+        terminal: Terminal,
+        reduce: (Key, Id),
+    },
+    ReduceReduce {
+        state: usize,
+        terminal: Terminal,
+        reduce: [(Key, Id); 2],
+    },
+    ShiftAccept {
+        state: usize,
+        terminal: Terminal,
+    },
+    AcceptReduce {
+        state: usize,
+        terminal: Terminal,
+        reduce: (Key, Id),
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShiftReduce {
+                state,
+                terminal,
+                reduce: (key, id),
+            } => write!(
+                f,
+                "shift/reduce conflict in state {state} on {terminal}: shift or reduce {key} {id}"
+            ),
+            Self::ReduceReduce {
+                state,
+                terminal,
+                reduce: [(first, first_id), (second, second_id)],
+            } => write!(
+                f,
+                "reduce/reduce conflict in state {state} on {terminal}: reduce {first} {first_id} or {second} {second_id}"
+            ),
+            Self::ShiftAccept { state, terminal } => write!(
+                f,
+                "shift/accept conflict in state {state} on {terminal}: shift or accept"
+            ),
+            Self::AcceptReduce {
+                state,
+                terminal,
+                reduce: (key, id),
+            } => write!(
+                f,
+                "accept/reduce conflict in state {state} on {terminal}: accept or reduce {key} {id}"
+            ),
+        }
+    }
+}
+
+/// The action and goto tables of a canonical LR(1) or LALR(1) automaton
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrTable {
+    entry: Key,
+    actions: Vec<HashMap<Terminal, Action>>,
+    gotos: Vec<HashMap<Key, usize>>,
+    conflicts: Vec<Conflict>,
+}
+
+impl LrTable {
+    pub fn entry(&self) -> &Key {
+        &self.entry
+    }
+
+    /// The number of states of the automaton
+    pub fn states(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn action(&self, state: usize, terminal: &Terminal) -> Option<&Action> {
+        self.actions[state].get(terminal)
+    }
+
+    pub fn goto(&self, state: usize, key: &Key) -> Option<usize> {
+        self.gotos[state].get(key).copied()
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+}
+
+impl fmt::Display for LrTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", "LR Table".bold())?;
+
+        for (state, (actions, gotos)) in self.actions.iter().zip(&self.gotos).enumerate() {
+            writeln!(f, "{}", state.italic())?;
+
+            for (terminal, action) in actions {
+                match action {
+                    Action::Shift(next) => writeln!(f, "\t{terminal}: shift {next}")?,
+                    Action::Reduce(key, id) => writeln!(f, "\t{terminal}: reduce {key} {id}")?,
+                    Action::Accept => writeln!(f, "\t{terminal}: accept")?,
+                }
+            }
+            for (key, next) in gotos {
+                writeln!(f, "\t{key}: goto {next}")?;
+            }
+        }
+
+        for conflict in &self.conflicts {
+            writeln!(f, "{}", conflict.red())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Grammar {
+    /// The canonical LR(1) table of the start symbol
+    pub fn lr1_table(&self) -> LrTable {
+        self.lr1_table_from(&self.start)
+    }
+
+    pub fn lr1_table_from(&self, entry: &Key) -> LrTable {
+        Automaton::new(self, entry).table(false)
+    }
+
+    /// The LALR(1) table of the start symbol, with the states of the same core merged
+    pub fn lalr1_table(&self) -> LrTable {
+        self.lalr1_table_from(&self.start)
+    }
+
+    pub fn lalr1_table_from(&self, entry: &Key) -> LrTable {
+        Automaton::new(self, entry).table(true)
+    }
+
+    /// Parses `terminals` with an LR table. The applied productions are
+    /// in the order of a leftmost derivation, just like for [`Grammar::parse`]
+    pub fn parse_lr(
+        &self,
+        table: &LrTable,
+        terminals: &[Terminal],
+    ) -> Result<Vec<(Key, Id)>, ParseError> {
+        self.parse_lr_tokens(table, Token::indexed(terminals).map(Ok))
+    }
+
+    pub fn parse_lr_tokens<E>(
+        &self,
+        table: &LrTable,
+        tokens: impl IntoIterator<Item = Result<Token, E>>,
+    ) -> Result<Vec<(Key, Id)>, ParseError<E>> {
+        let mut input = Lookahead::new(tokens.into_iter());
+        let mut states = vec![0];
+        // the reduced productions with their children, which are `None` for tokens
+        let mut nodes = Vec::<((Key, Id), Vec<usize>)>::new();
+        let mut values = Vec::<Option<usize>>::new();
+
+        loop {
+            let token = input.peek(1)?.remove(0);
+            let state = *states.last().unwrap();
+
+            match table.action(state, &token.terminal) {
+                Some(Action::Shift(next)) => {
+                    states.push(*next);
+                    values.push(None);
+                    input.bump();
+                }
+                Some(Action::Reduce(key, id)) => {
+                    let len = self.productions[key][id]
+                        .iter()
+                        .filter(|symbol| !symbol.is_epsilon())
+                        .count();
+                    states.truncate(states.len() - len);
+                    let children = values.drain(values.len() - len..).flatten().collect();
+
+                    nodes.push(((key.clone(), *id), children));
+                    values.push(Some(nodes.len() - 1));
+
+                    let state = *states.last().unwrap();
+                    states.push(table.goto(state, key).expect("goto of a reduction"));
+                }
+                Some(Action::Accept) => break,
+                None => {
+                    let mut expected = table.actions[state]
+                        .keys()
+                        .map(|terminal| vec![terminal.clone()])
+                        .collect::<Vec<_>>();
+                    expected.sort_by(|a, b| cmp_words_by_name(a, b));
+
+                    return Err(ParseError::Unexpected {
+                        terminal: token.terminal,
+                        expected,
+                        label: None,
+                        span: token.span,
+                        open: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        // the nodes are in postorder, the derivation is the preorder of the tree
+        let mut applied = Vec::with_capacity(nodes.len());
+        let mut pending = values.into_iter().flatten().rev().collect::<Vec<_>>();
+        while let Some(node) = pending.pop() {
+            let (production, children) = &nodes[node];
+            applied.push(production.clone());
+            pending.extend(children.iter().rev());
+        }
+
+        Ok(applied)
+    }
+}
+
+/// A production with a position in it, the production is `None` for `S' -> entry`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Item {
+    production: Option<(SymbolId, usize)>,
+    dot: usize,
+}
+
+/// The LR(1) items of a state, each with its lookahead terminal
+type State = BTreeSet<(Item, SymbolId)>;
+
+struct Automaton<'a> {
+    interned: &'a Interned,
+    entry: Key,
+    /// The right side of `S' -> entry`
+    augmented: [Sym; 1],
+    first: Vec<TerminalSet>,
+    nullable: Vec<bool>,
+    /// The position of each nonterminal when walking the productions depth first from the entry
+    declared: Vec<usize>,
+    states: Vec<State>,
+    transitions: Vec<HashMap<Sym, usize>>,
+}
+
+impl<'a> Automaton<'a> {
+    fn new(grammar: &'a Grammar, entry: &Key) -> Self {
+        grammar.assert_entry(entry);
+
+        let interned = grammar.interned();
+        let augmented = [Sym::Nonterminal(interned.nonterminal_id(entry))];
+
+        let mut first = vec![TerminalSet::new(); interned.rules.len()];
+        let mut nullable = vec![false; interned.rules.len()];
+        for (of, rule) in interned.first_k(1).into_iter().enumerate() {
+            for word in rule.into_iter().flat_map(|(_, words)| words) {
+                match word.first() {
+                    Some(terminal) => {
+                        first[of].insert(*terminal);
+                    }
+                    None => nullable[of] = true,
+                }
+            }
+        }
+
+        let mut declared = vec![usize::MAX; interned.rules.len()];
+        let mut pending = vec![interned.nonterminal_id(entry)];
+        let mut position = 0;
+        while let Some(nonterminal) = pending.pop() {
+            if declared[nonterminal.index()] != usize::MAX {
+                continue;
+            }
+            declared[nonterminal.index()] = position;
+            position += 1;
+
+            let rule = &interned.rules[nonterminal.index()];
+            for symbol in rule.iter().flat_map(|(_, symbols)| symbols).rev() {
+                if let Sym::Nonterminal(invoked) = symbol {
+                    pending.push(*invoked);
+                }
+            }
+        }
+
+        let mut automaton = Self {
+            interned,
+            entry: entry.clone(),
+            augmented,
+            first,
+            nullable,
+            declared,
+            states: Vec::new(),
+            transitions: Vec::new(),
+        };
+        automaton.build();
+        automaton
+    }
+
+    /// The key and id of a production given by its nonterminal and index
+    fn production(&self, (key, index): (SymbolId, usize)) -> (Key, Id) {
+        (
+            self.interned.nonterminals[key.index()].clone(),
+            self.interned.rules[key.index()][index].0,
+        )
+    }
+
+    /// The action of reducing by `production`, or of accepting for `S' -> entry`
+    fn reduce(&self, production: Option<(SymbolId, usize)>) -> Action {
+        match production {
+            Some(production) => {
+                let (key, id) = self.production(production);
+                Action::Reduce(key, id)
+            }
+            None => Action::Accept,
+        }
+    }
+
+    fn symbols(&self, item: &Item) -> &[Sym] {
+        match item.production {
+            Some((key, index)) => &self.interned.rules[key.index()][index].1,
+            None => &self.augmented,
+        }
+    }
+
+    /// FIRST_1 of `symbols` followed by `lookahead`
+    fn first_of(&self, symbols: &[Sym], lookahead: SymbolId) -> TerminalSet {
+        let mut set = TerminalSet::new();
+
+        for symbol in symbols {
+            match symbol {
+                Sym::Terminal(terminal) => {
+                    set.insert(*terminal);
+                    return set;
+                }
+                Sym::Nonterminal(nonterminal) => {
+                    set.union(&self.first[nonterminal.index()]);
+                    if !self.nullable[nonterminal.index()] {
+                        return set;
+                    }
+                }
+            }
+        }
+
+        set.insert(lookahead);
+        set
+    }
+
+    fn closure(&self, mut state: State) -> State {
+        let mut pending = state.iter().copied().collect::<Vec<_>>();
+
+        while let Some((item, lookahead)) = pending.pop() {
+            let symbols = self.symbols(&item);
+            let Some(Sym::Nonterminal(invoked)) = symbols.get(item.dot) else {
+                continue;
+            };
+
+            let lookaheads = self.first_of(&symbols[item.dot + 1..], lookahead);
+            for index in 0..self.interned.rules[invoked.index()].len() {
+                let item = Item {
+                    production: Some((*invoked, index)),
+                    dot: 0,
+                };
+                for lookahead in lookaheads.iter() {
+                    if state.insert((item, lookahead)) {
+                        pending.push((item, lookahead));
+                    }
+                }
+            }
+        }
+
+        state
+    }
+
+    /// Builds the canonical LR(1) collection
+    fn build(&mut self) {
+        let start = Item {
+            production: None,
+            dot: 0,
+        };
+        let start = self.closure(State::from([(start, self.interned.eoi())]));
+
+        let mut ids = HashMap::from([(start.clone(), 0)]);
+        self.states.push(start);
+
+        let mut next = 0;
+        while next < self.states.len() {
+            let mut kernels = HashMap::<Sym, State>::new();
+            for (item, lookahead) in &self.states[next] {
+                if let Some(symbol) = self.symbols(item).get(item.dot) {
+                    let item = Item {
+                        dot: item.dot + 1,
+                        ..*item
+                    };
+                    kernels
+                        .entry(*symbol)
+                        .or_default()
+                        .insert((item, *lookahead));
+                }
+            }
+
+            // sorted, so that the numbering of the states is deterministic
+            let mut kernels = kernels.into_iter().collect::<Vec<_>>();
+            kernels.sort();
+
+            let mut transitions = HashMap::new();
+            for (symbol, kernel) in kernels {
+                let state = self.closure(kernel);
+                let id = *ids.entry(state.clone()).or_insert_with(|| {
+                    self.states.push(state);
+                    self.states.len() - 1
+                });
+                transitions.insert(symbol, id);
+            }
+            self.transitions.push(transitions);
+
+            next += 1;
+        }
+    }
+
+    fn table(&self, merge: bool) -> LrTable {
+        // the states with the same items apart from the lookaheads are merged for LALR(1)
+        let mut merged = Vec::with_capacity(self.states.len());
+        let mut cores = HashMap::new();
+        for state in &self.states {
+            let core = state.iter().map(|(item, _)| *item).collect::<BTreeSet<_>>();
+            let len = cores.len();
+            merged.push(match merge {
+                true => *cores.entry(core).or_insert(len),
+                false => merged.len(),
+            });
+        }
+        let count = merged.iter().max().map_or(0, |max| max + 1);
+
+        let mut actions = vec![HashMap::new(); count];
+        let mut gotos = vec![HashMap::new(); count];
+        let mut conflicts = Vec::new();
+
+        for (from, transitions) in self.transitions.iter().enumerate() {
+            let id = merged[from];
+
+            let mut transitions = transitions.iter().collect::<Vec<_>>();
+            transitions.sort();
+            for (symbol, to) in transitions {
+                match symbol {
+                    Sym::Terminal(terminal) => {
+                        let terminal = self.interned.terminals[terminal.index()].clone();
+                        actions[id].insert(terminal, Action::Shift(merged[*to]));
+                    }
+                    Sym::Nonterminal(nonterminal) => {
+                        let key = self.interned.nonterminals[nonterminal.index()].clone();
+                        gotos[id].insert(key, merged[*to]);
+                    }
+                }
+            }
+        }
+
+        for (from, state) in self.states.iter().enumerate() {
+            let id = merged[from];
+
+            for (item, lookahead) in state {
+                if item.dot < self.symbols(item).len() {
+                    continue;
+                }
+
+                let terminal = self.interned.terminals[lookahead.index()].clone();
+                let Some(action) = actions[id].get_mut(&terminal) else {
+                    actions[id].insert(terminal, self.reduce(item.production));
+                    continue;
+                };
+
+                let conflict = match (action.clone(), item.production) {
+                    (Action::Shift(_), Some(production)) => Conflict::ShiftReduce {
+                        state: id,
+                        terminal,
+                        reduce: self.production(production),
+                    },
+                    (Action::Shift(_), None) => Conflict::ShiftAccept {
+                        state: id,
+                        terminal,
+                    },
+                    (Action::Accept, Some(production)) => Conflict::AcceptReduce {
+                        state: id,
+                        terminal,
+                        reduce: self.production(production),
+                    },
+                    (Action::Reduce(key, rid), None) => {
+                        *action = Action::Accept;
+                        Conflict::AcceptReduce {
+                            state: id,
+                            terminal,
+                            reduce: (key, rid),
+                        }
+                    }
+                    (Action::Reduce(key, rid), Some(production)) => {
+                        let production = self.production(production);
+                        if (&key, rid) == (&production.0, production.1) {
+                            continue;
+                        }
+
+                        let mut reduce = [(key, rid), production];
+                        reduce.sort_by_key(|(key, id)| {
+                            (
+                                self.declared[self.interned.nonterminal_id(key).index()],
+                                *id,
+                            )
+                        });
+                        *action = Action::Reduce(reduce[0].0.clone(), reduce[0].1);
+                        Conflict::ReduceReduce {
+                            state: id,
+                            terminal,
+                            reduce,
+                        }
+                    }
+                    (Action::Accept, None) => continue,
+                };
+
+                if !conflicts.contains(&conflict) {
+                    conflicts.push(conflict);
+                }
+            }
+        }
+
+        LrTable {
+            entry: self.entry.clone(),
+            actions,
+            gotos,
+            conflicts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Id, Key, Symbol, Terminal},
+        parser::ParseError,
+        span::Token,
+        test_util::{grammar, terminals},
+    };
+
+    use super::{Action, Conflict};
+
+    #[test]
+    fn lr_1() {
+        // E -> E plus T | T, T -> n | open E close
+        let grammar = grammar(&[
+            ("E", &[&["E", "plus", "T"], &["T"]]),
+            ("T", &[&["n"], &["open", "E", "close"]]),
+        ]);
+
+        let lr1 = grammar.lr1_table();
+        let lalr1 = grammar.lalr1_table();
+        assert!(lr1.conflicts().is_empty());
+        assert!(lalr1.conflicts().is_empty());
+        assert!(lalr1.states() < lr1.states());
+
+        let input = terminals(&["n", "plus", "open", "n", "close", "plus", "n"]);
+        let (e, t) = (Key::new("E"), Key::new("T"));
+        let expected = vec![
+            (e.clone(), Id(0)),
+            (e.clone(), Id(0)),
+            (e.clone(), Id(1)),
+            (t.clone(), Id(0)),
+            (t.clone(), Id(1)),
+            (e, Id(1)),
+            (t.clone(), Id(0)),
+            (t, Id(0)),
+        ];
+
+        for table in [&lr1, &lalr1] {
+            let applied = grammar.parse_lr(table, &input).unwrap();
+            assert_eq!(applied, expected);
+
+            let tokens = Token::indexed(&input).collect::<Vec<_>>();
+            assert_eq!(grammar.tree(&applied, &tokens).terminals(), input);
+        }
+
+        let err = grammar
+            .parse_lr(&lalr1, &terminals(&["n", "plus"]))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::Unexpected { terminal, expected, .. }
+                if terminal.is_eoi() && expected == vec![terminals(&["n"]), terminals(&["open"])]
+        ));
+    }
+
+    #[test]
+    fn lr_2() {
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+        let input = [uint.clone(), uint.clone(), boolean, uint];
+
+        let table = grammar.lalr1_table();
+        assert!(table.conflicts().is_empty());
+        assert_eq!(
+            grammar.parse_lr(&table, &input).unwrap(),
+            grammar.parse(1, &input).unwrap()
+        );
+    }
+
+    #[test]
+    fn lr_3() {
+        // ambiguous: E -> E plus E | n
+        let ambiguous = grammar(&[("E", &[&["E", "plus", "E"], &["n"]])]);
+        assert_eq!(
+            ambiguous.lalr1_table().conflicts(),
+            [Conflict::ShiftReduce {
+                state: 4,
+                terminal: Terminal::from(Key::new("plus")),
+                reduce: (Key::new("E"), Id(0)),
+            }]
+        );
+
+        // LR(1) but not LALR(1): S -> a A d | b B d | a B e | b A e, A -> c, B -> c
+        let grammar = grammar(&[
+            (
+                "S",
+                &[
+                    &["a", "A", "d"],
+                    &["b", "B", "d"],
+                    &["a", "B", "e"],
+                    &["b", "A", "e"],
+                ],
+            ),
+            ("A", &[&["c"]]),
+            ("B", &[&["c"]]),
+        ]);
+        assert!(grammar.lr1_table().conflicts().is_empty());
+
+        let conflicts = grammar.lalr1_table().conflicts().to_vec();
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts.iter().all(|conflict| matches!(
+            conflict,
+            Conflict::ReduceReduce { reduce, .. }
+                if *reduce == [(Key::new("A"), Id(0)), (Key::new("B"), Id(0))]
+        )));
+
+        // the canonical table still tells the reductions apart
+        let applied = grammar
+            .parse_lr(&grammar.lr1_table(), &terminals(&["a", "c", "e"]))
+            .unwrap();
+        assert_eq!(
+            applied,
+            vec![(Key::new("S"), Id(2)), (Key::new("B"), Id(0))]
+        );
+    }
+
+    #[test]
+    fn lr_4() {
+        // the first reduction of a reduce/reduce conflict is the one declared first:
+        // S -> a Y d | b X d | a X e | b Y e, Y -> c, X -> c
+        let declared = grammar(&[
+            (
+                "S",
+                &[
+                    &["a", "Y", "d"],
+                    &["b", "X", "d"],
+                    &["a", "X", "e"],
+                    &["b", "Y", "e"],
+                ],
+            ),
+            ("Y", &[&["c"]]),
+            ("X", &[&["c"]]),
+        ]);
+        let table = declared.lalr1_table();
+        assert!(!table.conflicts().is_empty());
+        for conflict in table.conflicts() {
+            let Conflict::ReduceReduce {
+                state,
+                terminal,
+                reduce,
+            } = conflict
+            else {
+                panic!("{conflict}");
+            };
+            assert_eq!(*reduce, [(Key::new("Y"), Id(0)), (Key::new("X"), Id(0))]);
+            assert_eq!(
+                table.action(*state, terminal),
+                Some(&Action::Reduce(Key::new("Y"), Id(0)))
+            );
+        }
+
+        // S -> S | a, after S the parse may accept or reduce S -> S
+        let cyclic = grammar(&[("S", &[&["S"], &["a"]])]);
+        let table = cyclic.lr1_table();
+        let [Conflict::AcceptReduce {
+            state,
+            terminal,
+            reduce,
+        }] = table.conflicts()
+        else {
+            panic!("{:?}", table.conflicts());
+        };
+        assert!(terminal.is_eoi());
+        assert_eq!(*reduce, (Key::new("S"), Id(0)));
+        assert_eq!(table.action(*state, terminal), Some(&Action::Accept));
+
+        // S -> S $ | a, after S the parse may accept or shift the end of the input
+        let mut explicit = grammar(&[("S", &[&["S"], &["a"]])]);
+        explicit[&Key::new("S")][&Id(0)].push(Symbol::Terminal(Terminal::eoi()));
+        let table = explicit.lr1_table();
+        let [Conflict::ShiftAccept { state, terminal }] = table.conflicts() else {
+            panic!("{:?}", table.conflicts());
+        };
+        assert!(terminal.is_eoi());
+        assert!(matches!(
+            table.action(*state, terminal),
+            Some(Action::Shift(_))
+        ));
+    }
+}
@@ -1,79 +1,396 @@
-use crate::grammar::{Grammar, Id, Key, Symbol, Terminal};
+use crate::{
+    grammar::{Grammar, Id, Key, Symbol, Terminal},
+    span::{Span, Token},
+    table::{Row, Table},
+    tree::Tree,
+};
+use ecow::EcoString;
+use std::{collections::VecDeque, convert::Infallible};
 use thiserror::Error;
 
-#[derive(Debug, Error)]
-pub enum ParseError {
-    #[error("Unexpected {terminal:?} while parsing, expected one of {expected:?}")]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError<E = Infallible> {
+    #[error("Unexpected {terminal:?} at {span} while parsing, expected one of {expected:?}")]
     Unexpected {
         terminal: Terminal,
         expected: Vec<Vec<Terminal>>,
+        /// A summary of `expected` from the labels of the grammar
+        label: Option<EcoString>,
+        span: Span,
+        /// The productions which were not completed yet, outermost first,
+        /// with the span of the token they started at
+        open: Vec<(Key, Id, Span)>,
     },
+    #[error("Lexer error while parsing: {0}")]
+    Lexer(E),
+    #[error("{0} is no nonterminal of the grammar to start the parse at")]
+    UnknownEntry(Key),
+}
+
+impl<E> ParseError<E> {
+    /// The span of the offending token, lexer errors have to provide their own
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Unexpected { span, .. } => Some(*span),
+            Self::Lexer(_) | Self::UnknownEntry(_) => None,
+        }
+    }
 }
 
 impl Grammar {
     pub fn parse(&self, k: usize, terminals: &[Terminal]) -> Result<Vec<(Key, Id)>, ParseError> {
-        let table = self.table(k);
+        self.parse_from(k, &self.start, terminals)
+    }
 
-        let mut applied = Vec::new();
-
-        let start = Symbol::nonterminal(self.start.clone());
-        let mut stack = vec![start];
-        let mut cursor = 0;
-
-        loop {
-            let current = stack.pop().unwrap().into_nonterminal().unwrap().0;
-            let look_ahead = &table[&current];
-
-            let &id = (1..=k)
-                .into_iter()
-                .find_map(|i| {
-                    let peek = &terminals[cursor..(i + cursor)];
-                    look_ahead.get(peek)
-                })
-                .ok_or(ParseError::Unexpected {
-                    terminal: terminals[cursor].clone(),
-                    expected: look_ahead.keys().cloned().collect(),
-                })?;
+    pub fn parse_as<T: ?Sized + 'static>(
+        &self,
+        k: usize,
+        terminals: &[Terminal],
+    ) -> Result<Vec<(Key, Id)>, ParseError> {
+        self.parse_from(k, &Key::of::<T>(), terminals)
+    }
+
+    /// Parses `terminals` as a whole instance of the `entry` nonterminal
+    pub fn parse_from(
+        &self,
+        k: usize,
+        entry: &Key,
+        terminals: &[Terminal],
+    ) -> Result<Vec<(Key, Id)>, ParseError> {
+        self.parse_tokens_from(k, entry, Token::indexed(terminals).map(Ok))
+    }
+
+    /// Parses the tokens of a (lexer) iterator, only buffering `k` of them at a time
+    pub fn parse_tokens<E>(
+        &self,
+        k: usize,
+        tokens: impl IntoIterator<Item = Result<Token, E>>,
+    ) -> Result<Vec<(Key, Id)>, ParseError<E>> {
+        self.parse_tokens_from(k, &self.start, tokens)
+    }
+
+    pub fn parse_tokens_from<E>(
+        &self,
+        k: usize,
+        entry: &Key,
+        tokens: impl IntoIterator<Item = Result<Token, E>>,
+    ) -> Result<Vec<(Key, Id)>, ParseError<E>> {
+        self.check_entry(entry)?;
+        self.parse_with_table(&self.table_from(k, entry), k, entry, tokens)
+    }
+
+    /// Parses with a table built before, so that it is not built for every input.
+    /// `table` must have been built with the same `k` for `entry`, see [`Grammar::table_from`]
+    pub fn parse_with_table<E>(
+        &self,
+        table: &Table,
+        k: usize,
+        entry: &Key,
+        tokens: impl IntoIterator<Item = Result<Token, E>>,
+    ) -> Result<Vec<(Key, Id)>, ParseError<E>> {
+        let mut input = Lookahead::new(tokens.into_iter());
+
+        let applied = self.parse_with(table, k, entry, &mut input, false)?;
+
+        let token = input.peek(1)?.remove(0);
+        if !token.terminal.is_eoi() {
+            return Err(ParseError::Unexpected {
+                terminal: token.terminal,
+                expected: vec![vec![Terminal::eoi()]],
+                label: None,
+                span: token.span,
+                open: Vec::new(),
+            });
+        }
 
-            for symbol in &self.productions[&current][&id] {
-                stack.push(symbol.clone());
+        Ok(applied)
+    }
+
+    /// Parses the longest instance of the start symbol at the front of `terminals`
+    /// and returns the index of the first terminal which was not consumed
+    pub fn parse_prefix(
+        &self,
+        k: usize,
+        terminals: &[Terminal],
+    ) -> Result<(Vec<(Key, Id)>, usize), ParseError> {
+        self.parse_prefix_from(k, &self.start, terminals)
+    }
+
+    pub fn parse_prefix_from(
+        &self,
+        k: usize,
+        entry: &Key,
+        terminals: &[Terminal],
+    ) -> Result<(Vec<(Key, Id)>, usize), ParseError> {
+        self.check_entry(entry)?;
+        let table = self.table_from(k, entry);
+        let mut input = Lookahead::new(Token::indexed(terminals).map(Ok));
+
+        let applied = self.parse_with(&table, k, entry, &mut input, true)?;
+        Ok((applied, input.consumed))
+    }
+
+    /// Repeatedly parses the start symbol from the front of `terminals`,
+    /// stopping after the input is consumed or the first error
+    pub fn parse_iter<'a>(
+        &'a self,
+        k: usize,
+        terminals: &'a [Terminal],
+    ) -> impl Iterator<Item = Result<Tree, ParseError>> + 'a {
+        let table = self.table(k);
+        let mut input = Lookahead::new(Token::indexed(terminals).map(Ok));
+        let mut failed = false;
+
+        std::iter::from_fn(move || {
+            if failed || input.consumed == terminals.len() {
+                return None;
             }
 
-            while let Some(symbol) = stack.pop() {
-                match symbol {
-                    Symbol::Epsilon => (),
-                    Symbol::Terminal(terminal) => {
-                        assert_eq!(&terminal, &terminals[cursor]);
-                        cursor += 1;
+            let cursor = input.consumed;
+            let result = self
+                .parse_with(&table, k, &self.start, &mut input, true)
+                .and_then(|applied| {
+                    if input.consumed == cursor {
+                        // nothing would ever be consumed again
+                        return Err(ParseError::Unexpected {
+                            terminal: terminals[cursor].clone(),
+                            expected: table[&self.start].keys().cloned().collect(),
+                            label: None,
+                            span: Span::new(cursor, cursor + 1),
+                            open: Vec::new(),
+                        });
                     }
-                    Symbol::Nonterminal(_) => {
-                        stack.push(symbol);
-                        applied.push((current.clone(), id));
-                        break;
+
+                    let tokens = Token::indexed(terminals)
+                        .skip(cursor)
+                        .take(input.consumed - cursor)
+                        .collect::<Vec<_>>();
+                    Ok(self.tree(&applied, &tokens))
+                });
+
+            failed = result.is_err();
+            Some(result)
+        })
+    }
+
+    /// If `prefix` is set, the input may end after any terminal of `input`,
+    /// as long as the lookahead of the real input does not allow to continue
+    pub(crate) fn parse_with<I, E>(
+        &self,
+        table: &Table,
+        k: usize,
+        entry: &Key,
+        input: &mut Lookahead<I>,
+        prefix: bool,
+    ) -> Result<Vec<(Key, Id)>, ParseError<E>>
+    where
+        I: Iterator<Item = Result<Token, E>>,
+    {
+        self.check_entry(entry)?;
+
+        let mut state = State::new(entry.clone());
+
+        while let Some(n) = state.needs(k) {
+            let peek = input.peek(n)?;
+            if state.step(self, table, &peek, prefix)? {
+                input.bump();
+            }
+        }
+
+        Ok(state.applied)
+    }
+}
+
+/// The symbol stack and applied productions of a table driven parse
+#[derive(Debug, Clone)]
+pub(crate) struct State {
+    pub(crate) stack: Vec<Symbol>,
+    pub(crate) applied: Vec<(Key, Id)>,
+    /// The started productions with the height of the stack below their symbols
+    open: Vec<(Key, Id, Span, usize)>,
+}
+
+impl State {
+    pub(crate) fn new(entry: Key) -> Self {
+        Self {
+            stack: vec![Symbol::nonterminal(entry)],
+            applied: Vec::new(),
+            open: Vec::new(),
+        }
+    }
+
+    fn open(&self) -> Vec<(Key, Id, Span)> {
+        self.open
+            .iter()
+            .map(|(key, id, span, _)| (key.clone(), *id, *span))
+            .collect()
+    }
+
+    /// The length of the lookahead needed for the next step, if the parse is not done yet
+    pub(crate) fn needs(&self, k: usize) -> Option<usize> {
+        self.stack.last().map(|symbol| match symbol {
+            Symbol::Nonterminal(_) => k,
+            _ => 1,
+        })
+    }
+
+    /// Processes the top of the stack and returns whether the first token of `peek` was consumed
+    pub(crate) fn step<E>(
+        &mut self,
+        grammar: &Grammar,
+        table: &Table,
+        peek: &[Token],
+        prefix: bool,
+    ) -> Result<bool, ParseError<E>> {
+        let consumed = self.pop(grammar, table, peek, prefix)?;
+
+        // productions are completed once all of their symbols left the stack
+        while let Some((_, _, _, height)) = self.open.last() {
+            if *height < self.stack.len() {
+                break;
+            }
+            self.open.pop();
+        }
+
+        Ok(consumed)
+    }
+
+    fn pop<E>(
+        &mut self,
+        grammar: &Grammar,
+        table: &Table,
+        peek: &[Token],
+        prefix: bool,
+    ) -> Result<bool, ParseError<E>> {
+        match self.stack.pop() {
+            None | Some(Symbol::Epsilon) => Ok(false),
+            Some(Symbol::Terminal(terminal)) => {
+                if peek[0].terminal != terminal {
+                    return Err(ParseError::Unexpected {
+                        terminal: peek[0].terminal.clone(),
+                        expected: vec![vec![terminal]],
+                        label: None,
+                        span: peek[0].span,
+                        open: self.open(),
+                    });
+                }
+                Ok(true)
+            }
+            Some(Symbol::Nonterminal(nonterminal)) => {
+                let current = nonterminal.0;
+                let look_ahead = &table[&current];
+                let terminals = peek
+                    .iter()
+                    .map(|token| token.terminal.clone())
+                    .collect::<Vec<_>>();
+
+                let mut found = lookup(look_ahead, &terminals);
+
+                if prefix {
+                    // pretend the input ends early, preferring the longest remaining input
+                    let mut len =
+                        terminals.len() - terminals.last().map_or(0, |t| t.is_eoi() as usize);
+                    while found.is_none() && len > 0 {
+                        len -= 1;
+                        let mut cut = terminals[..len].to_vec();
+                        cut.push(Terminal::eoi());
+                        found = lookup(look_ahead, &cut);
                     }
                 }
+
+                let id = found.ok_or_else(|| {
+                    let mut candidates = look_ahead.values().copied().collect::<Vec<_>>();
+                    candidates.sort();
+                    candidates.dedup();
+
+                    ParseError::Unexpected {
+                        terminal: terminals[0].clone(),
+                        expected: look_ahead.keys().cloned().collect(),
+                        label: grammar
+                            .expected_label(&current, &candidates)
+                            .map(EcoString::from),
+                        span: peek[0].span,
+                        open: self.open(),
+                    }
+                })?;
+
+                self.open
+                    .push((current.clone(), id, peek[0].span, self.stack.len()));
+                self.stack
+                    .extend(grammar.productions[&current][&id].iter().rev().cloned());
+                self.applied.push((current, id));
+
+                Ok(false)
             }
+        }
+    }
+}
 
-            if stack.is_empty() {
-                applied.push((current, id));
-                break;
+/// Buffers the tokens of `tokens` which are needed for the lookahead
+pub(crate) struct Lookahead<I> {
+    tokens: I,
+    buffer: VecDeque<Token>,
+    pub(crate) consumed: usize,
+    end: usize,
+}
+
+impl<I, E> Lookahead<I>
+where
+    I: Iterator<Item = Result<Token, E>>,
+{
+    pub(crate) fn new(tokens: I) -> Self {
+        Self {
+            tokens,
+            buffer: VecDeque::new(),
+            consumed: 0,
+            end: 0,
+        }
+    }
+
+    /// The next `k` tokens, terminated by the end of input if there are less left
+    pub(crate) fn peek(&mut self, k: usize) -> Result<Vec<Token>, ParseError<E>> {
+        while self.buffer.len() < k {
+            match self.tokens.next() {
+                Some(Ok(token)) => {
+                    self.end = token.span.end;
+                    self.buffer.push_back(token);
+                }
+                Some(Err(e)) => return Err(ParseError::Lexer(e)),
+                None => break,
             }
         }
 
-        assert_eq!(cursor, terminals.len());
+        let mut peek = self.buffer.iter().take(k).cloned().collect::<Vec<_>>();
+        if peek.len() < k {
+            peek.push(Token::new(Terminal::eoi(), Span::empty(self.end)));
+        }
+        Ok(peek)
+    }
 
-        Ok(applied)
+    pub(crate) fn bump(&mut self) {
+        self.buffer.pop_front();
+        self.consumed += 1;
     }
 }
 
+fn lookup(look_ahead: &Row, peek: &[Terminal]) -> Option<Id> {
+    (1..=peek.len()).find_map(|i| look_ahead.get(&peek[..i]).copied())
+}
+
 #[cfg(test)]
 mod test {
 
+    use std::convert::Infallible;
+
     use crate::{
         builder::Syntactical,
         grammar::{Grammar, Id, Key, Rule, Symbol, Terminal},
+        push::PushParser,
+        span::{Span, Token},
     };
 
+    use super::ParseError;
+
     #[allow(dead_code)]
     enum S {
         A((u8, A, u8)),
@@ -247,4 +564,266 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn parse_from_1() {
+        let mut grammar = Grammar::new(Key::of::<S>());
+        let mut stack = Vec::new();
+
+        S::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let rules = grammar
+            .parse_as::<A>(1, &[boolean.clone(), uint.clone(), uint, boolean])
+            .unwrap();
+
+        let a = Key::of::<A>();
+        let s = Key::of::<S>();
+
+        assert_eq!(rules, vec![(a.clone(), Id(0)), (s, Id(0)), (a, Id(1))]);
+    }
+
+    #[test]
+    fn parse_from_2() {
+        let mut grammar = Grammar::new(Key::of::<S>());
+        let mut stack = Vec::new();
+
+        S::generate(&mut grammar, &mut stack);
+
+        let boolean = Terminal(Key::of::<bool>());
+
+        let rules = grammar.parse_from(2, &Key::of::<A>(), &[]).unwrap();
+        assert_eq!(rules, vec![(Key::of::<A>(), Id(1))]);
+
+        assert!(grammar.parse_as::<A>(2, &[boolean]).is_err());
+    }
+
+    #[test]
+    fn parse_from_3() {
+        let mut grammar = Grammar::new(Key::of::<S>());
+        let mut stack = Vec::new();
+
+        S::generate(&mut grammar, &mut stack);
+
+        let input = [Terminal(Key::of::<u8>())];
+        let unknown = Key::of::<char>();
+        let error = ParseError::UnknownEntry(unknown.clone());
+
+        assert_eq!(grammar.parse_from(1, &unknown, &input), Err(error.clone()));
+        assert_eq!(
+            grammar.parse_prefix_from(1, &unknown, &input),
+            Err(error.clone())
+        );
+        assert_eq!(grammar.forest_from(&unknown, &input).unwrap_err(), error);
+
+        let table = grammar.table(1);
+        assert_eq!(
+            grammar.parse_with_table(&table, 1, &unknown, Vec::<Result<Token, Infallible>>::new()),
+            Err(error.clone())
+        );
+        assert_eq!(
+            PushParser::with_entry(&grammar, &table, 1, unknown).unwrap_err(),
+            error
+        );
+        assert_eq!(
+            grammar.diagnostic(&error).message,
+            "`char` is no nonterminal of the grammar"
+        );
+    }
+
+    #[test]
+    fn parse_prefix_1() {
+        let mut grammar = Grammar::new(Key::of::<S>());
+        let mut stack = Vec::new();
+
+        S::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let (rules, rest) = grammar
+            .parse_prefix(1, &[uint.clone(), uint.clone(), uint, boolean])
+            .unwrap();
+
+        let a = Key::of::<A>();
+        let s = Key::of::<S>();
+
+        assert_eq!(rules, vec![(s, Id(0)), (a, Id(1))]);
+        assert_eq!(rest, 2);
+    }
+
+    #[test]
+    fn parse_prefix_2() {
+        let mut grammar = Grammar::new(Key::of::<Vec<u8>>());
+        let mut stack = Vec::new();
+
+        Vec::<u8>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let vec = Key::of::<Vec<u8>>();
+
+        for k in 1..=3 {
+            let (rules, rest) = grammar
+                .parse_prefix(k, &[uint.clone(), uint.clone(), boolean.clone()])
+                .unwrap();
+
+            assert_eq!(
+                rules,
+                vec![
+                    (vec.clone(), Id(0)),
+                    (vec.clone(), Id(0)),
+                    (vec.clone(), Id(1))
+                ]
+            );
+            assert_eq!(rest, 2);
+        }
+
+        assert!(grammar.parse(1, &[uint, boolean]).is_err());
+    }
+
+    #[test]
+    fn parse_iter_1() {
+        let mut grammar = Grammar::new(Key::of::<S>());
+        let mut stack = Vec::new();
+
+        S::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let input = [
+            uint.clone(),
+            uint.clone(),
+            uint.clone(),
+            boolean.clone(),
+            uint.clone(),
+            uint.clone(),
+            boolean.clone(),
+            uint.clone(),
+            boolean.clone(),
+        ];
+
+        let mut items = grammar.parse_iter(1, &input);
+
+        let first = items.next().unwrap().unwrap();
+        assert_eq!(first.terminals(), vec![uint.clone(), uint.clone()]);
+
+        let second = items.next().unwrap().unwrap();
+        assert_eq!(second.terminals(), input[2..8].to_vec());
+        assert_eq!(
+            second.derivation(),
+            vec![
+                (Key::of::<S>(), Id(0)),
+                (Key::of::<A>(), Id(0)),
+                (Key::of::<S>(), Id(0)),
+                (Key::of::<A>(), Id(1)),
+            ]
+        );
+
+        assert!(items.next().unwrap().is_err());
+        assert!(items.next().is_none());
+    }
+
+    #[test]
+    fn parse_tokens_1() {
+        let mut grammar = Grammar::new(Key::of::<Vec<u8>>());
+        let mut stack = Vec::new();
+
+        Vec::<u8>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let vec = Key::of::<Vec<u8>>();
+
+        let tokens = (0..1000).map(|i| Ok::<_, String>(Token::new(uint.clone(), 2 * i..2 * i + 1)));
+        let rules = grammar.parse_tokens(2, tokens).unwrap();
+        assert_eq!(rules.len(), 1001);
+        assert_eq!(rules.last(), Some(&(vec, Id(1))));
+    }
+
+    #[test]
+    fn parse_tokens_2() {
+        let mut grammar = Grammar::new(Key::of::<Vec<u8>>());
+        let mut stack = Vec::new();
+
+        Vec::<u8>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+
+        let tokens = vec![
+            Ok(Token::new(uint.clone(), 0..1)),
+            Ok(Token::new(uint, 1..2)),
+            Err("invalid character"),
+        ];
+        let err = grammar.parse_tokens(1, tokens).unwrap_err();
+        assert!(matches!(err, ParseError::Lexer("invalid character")));
+        assert_eq!(err.span(), None);
+    }
+
+    #[test]
+    fn parse_span_1() {
+        let mut grammar = Grammar::new(Key::of::<S>());
+        let mut stack = Vec::new();
+
+        S::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        // "1 true"
+        let tokens = vec![
+            Ok::<_, Infallible>(Token::new(uint.clone(), 0..1)),
+            Ok(Token::new(boolean.clone(), 2..6)),
+        ];
+        let err = grammar.parse_tokens(1, tokens).unwrap_err();
+        assert_eq!(err.span(), Some(Span::empty(6)));
+
+        // "1 1 true"
+        let tokens = vec![
+            Ok::<_, Infallible>(Token::new(uint.clone(), 0..1)),
+            Ok(Token::new(uint, 2..3)),
+            Ok(Token::new(boolean.clone(), 4..8)),
+        ];
+        let err = grammar.parse_tokens(1, tokens).unwrap_err();
+        assert_eq!(err.span(), Some(Span::new(4, 8)));
+        assert!(matches!(err, ParseError::Unexpected { terminal, .. } if terminal == boolean));
+    }
+
+    #[test]
+    fn parse_label_1() {
+        let mut grammar = Grammar::new(Key::of::<S>());
+        let mut stack = Vec::new();
+
+        S::generate(&mut grammar, &mut stack);
+
+        let other = Terminal(Key::of::<char>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let label = |grammar: &Grammar, entry, terminal: &Terminal| match grammar.parse_from(
+            1,
+            &entry,
+            std::slice::from_ref(terminal),
+        ) {
+            Err(ParseError::Unexpected { label, .. }) => label,
+            _ => unreachable!(),
+        };
+
+        grammar.set_expected(Key::of::<A>(), Some(Id(0)), "flag");
+        assert_eq!(label(&grammar, Key::of::<A>(), &other), None);
+
+        grammar.set_expected(Key::of::<A>(), Some(Id(1)), "flag");
+        assert_eq!(
+            label(&grammar, Key::of::<A>(), &other).as_deref(),
+            Some("flag")
+        );
+
+        grammar.set_expected(Key::of::<S>(), None, "pair");
+        assert_eq!(
+            label(&grammar, Key::of::<S>(), &boolean).as_deref(),
+            Some("pair")
+        );
+    }
 }
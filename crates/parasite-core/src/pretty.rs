@@ -0,0 +1,188 @@
+/// The indentation of nested documents in the default layouts
+pub const INDENT: usize = 4;
+
+/// A document in the style of Wadler's "A prettier printer",
+/// whose groups are laid out flat if they fit the line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Doc {
+    Text(String),
+    /// A space, or a new line if the enclosing group is broken
+    Line,
+    /// Nothing, or a new line if the enclosing group is broken
+    SoftLine,
+    /// Always a new line, so the enclosing groups are broken as well
+    HardLine,
+    Concat(Vec<Doc>),
+    /// Indents the new lines of the document
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn concat(docs: impl IntoIterator<Item = Doc>) -> Self {
+        Self::Concat(docs.into_iter().collect())
+    }
+
+    pub fn nest(self, indent: usize) -> Self {
+        Self::Nest(indent, Box::new(self))
+    }
+
+    pub fn group(self) -> Self {
+        Self::Group(Box::new(self))
+    }
+
+    /// Lays out the document on a single line, only hard lines are kept
+    pub fn flatten(self) -> Self {
+        match self {
+            Self::Line => Self::text(" "),
+            Self::SoftLine => Self::Concat(Vec::new()),
+            Self::Concat(docs) => Self::concat(docs.into_iter().map(Self::flatten)),
+            Self::Nest(indent, doc) => doc.flatten().nest(indent),
+            Self::Group(doc) => doc.flatten(),
+            doc => doc,
+        }
+    }
+
+    /// Renders the document, breaking the groups which do not fit in `width` columns
+    pub fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        // the spaces at the end of `out` the renderer wrote itself, not the text
+        let mut spaces = 0;
+        let mut stack = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Self::Text(text) => {
+                    out.push_str(text);
+                    if !text.is_empty() {
+                        spaces = 0;
+                    }
+                    column = match text.rfind('\n') {
+                        Some(line) => text[line + 1..].chars().count(),
+                        None => column + text.chars().count(),
+                    };
+                }
+                Self::Line if mode == Mode::Flat => {
+                    out.push(' ');
+                    column += 1;
+                    spaces += 1;
+                }
+                Self::SoftLine if mode == Mode::Flat => (),
+                Self::Line | Self::SoftLine | Self::HardLine => {
+                    out.truncate(out.len() - spaces);
+                    out.push('\n');
+                    out.extend(std::iter::repeat_n(' ', indent));
+                    column = indent;
+                    spaces = indent;
+                }
+                Self::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)))
+                }
+                Self::Nest(nested, doc) => stack.push((indent + nested, mode, doc)),
+                Self::Group(doc) => {
+                    let flat = mode == Mode::Flat
+                        || fits(width as isize - column as isize, (indent, doc), &stack);
+                    let mode = if flat { Mode::Flat } else { Mode::Break };
+                    stack.push((indent, mode, doc));
+                }
+            }
+        }
+
+        out.truncate(out.len() - spaces);
+        out
+    }
+}
+
+/// Whether `doc` laid out flat and the rest up to its next line break fit in `width`
+fn fits(mut width: isize, (indent, doc): (usize, &Doc), rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(indent, Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    while width >= 0 {
+        let Some((indent, mode, doc)) = stack.pop().or_else(|| rest.next().copied()) else {
+            return true;
+        };
+
+        match doc {
+            Doc::Text(text) => match text.split_once('\n') {
+                Some((line, _)) => return width >= line.chars().count() as isize,
+                None => width -= text.chars().count() as isize,
+            },
+            Doc::Line if mode == Mode::Flat => width -= 1,
+            Doc::SoftLine if mode == Mode::Flat => (),
+            Doc::HardLine if mode == Mode::Flat => return false,
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            Doc::Nest(nested, doc) => stack.push((indent + nested, mode, doc)),
+            Doc::Group(doc) => stack.push((indent, mode, doc)),
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Doc;
+
+    #[test]
+    fn pretty_1() {
+        // [a, b, c] with the elements indented when broken
+        let list = |names: &[&str]| {
+            let mut elements = Vec::new();
+            for (index, name) in names.iter().enumerate() {
+                if index > 0 {
+                    elements.push(Doc::text(","));
+                    elements.push(Doc::Line);
+                }
+                elements.push(Doc::text(*name));
+            }
+            Doc::concat([
+                Doc::text("["),
+                Doc::concat([Doc::SoftLine, Doc::concat(elements).group()]).nest(2),
+                Doc::SoftLine,
+                Doc::text("]"),
+            ])
+            .group()
+        };
+
+        let doc = list(&["alpha", "beta", "gamma"]);
+        assert_eq!(doc.render(80), "[alpha, beta, gamma]");
+        assert_eq!(doc.render(20), "[alpha, beta, gamma]");
+        assert_eq!(doc.render(10), "[\n  alpha,\n  beta,\n  gamma\n]");
+
+        // only the outer group is broken if the elements fit on their own line
+        let call = Doc::concat([Doc::text("list"), doc.clone()]);
+        assert_eq!(call.render(22), "list[\n  alpha, beta, gamma\n]");
+
+        let doc = Doc::concat([Doc::text("f"), doc, Doc::HardLine, Doc::text("g")]).group();
+        assert_eq!(doc.render(80), "f[alpha, beta, gamma]\ng");
+        assert_eq!(doc.clone().flatten().render(10), doc.flatten().render(80));
+    }
+
+    #[test]
+    fn pretty_2() {
+        // the spaces of the text stay, only the indentation of empty lines goes
+        let doc = Doc::concat([
+            Doc::text("a  "),
+            Doc::HardLine,
+            Doc::HardLine,
+            Doc::text("b"),
+            Doc::HardLine,
+        ])
+        .nest(2);
+        assert_eq!(doc.render(80), "a  \n\n  b\n");
+    }
+}
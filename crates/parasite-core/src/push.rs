@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+
+use crate::{
+    grammar::{Grammar, Id, Key, Terminal},
+    parser::{ParseError, State},
+    span::{Span, Token},
+    table::Table,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// More terminals are needed before the parse can continue
+    NeedMore,
+    /// The entry was parsed completely, only `finish` is left to do
+    Done,
+    Error(ParseError),
+}
+
+/// A parser which is fed one terminal at a time.
+/// It can be cloned at any point to speculatively continue the parse.
+#[derive(Debug, Clone)]
+pub struct PushParser<'g> {
+    grammar: &'g Grammar,
+    table: &'g Table,
+    k: usize,
+    state: State,
+    buffer: VecDeque<Token>,
+    consumed: usize,
+    end: usize,
+    error: Option<ParseError>,
+}
+
+impl<'g> PushParser<'g> {
+    /// `table` must have been built with the same `k` for the start of `grammar`
+    pub fn new(grammar: &'g Grammar, table: &'g Table, k: usize) -> Result<Self, ParseError> {
+        Self::with_entry(grammar, table, k, grammar.start.clone())
+    }
+
+    /// `table` must have been built with the same `k` for `entry`, see `Grammar::table_from`
+    pub fn with_entry(
+        grammar: &'g Grammar,
+        table: &'g Table,
+        k: usize,
+        entry: Key,
+    ) -> Result<Self, ParseError> {
+        grammar.check_entry(&entry)?;
+
+        Ok(Self {
+            grammar,
+            table,
+            k,
+            state: State::new(entry),
+            buffer: VecDeque::new(),
+            consumed: 0,
+            end: 0,
+            error: None,
+        })
+    }
+
+    pub fn feed(&mut self, token: Token) -> Status {
+        if self.error.is_none() {
+            self.end = token.span.end;
+            self.buffer.push_back(token);
+            self.advance(false);
+        }
+
+        self.status()
+    }
+
+    /// Marks the end of the input and returns the applied productions,
+    /// or the error the parse already stopped at
+    pub fn finish(mut self) -> Result<Vec<(Key, Id)>, ParseError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        self.advance(true);
+
+        match self.status() {
+            Status::Done => Ok(self.state.applied),
+            Status::Error(e) => Err(e),
+            Status::NeedMore => unreachable!(),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        if let Some(e) = &self.error {
+            Status::Error(e.clone())
+        } else if self.state.stack.is_empty() {
+            Status::Done
+        } else {
+            Status::NeedMore
+        }
+    }
+
+    /// The number of terminals consumed so far
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// The productions applied so far, in the order of a leftmost derivation
+    pub fn applied(&self) -> &[(Key, Id)] {
+        &self.state.applied
+    }
+
+    fn advance(&mut self, ended: bool) {
+        while let Some(n) = self.state.needs(self.k) {
+            if self.buffer.len() < n && !ended {
+                return;
+            }
+
+            let mut peek = self.buffer.iter().take(n).cloned().collect::<Vec<_>>();
+            if peek.len() < n {
+                peek.push(Token::new(Terminal::eoi(), Span::empty(self.end)));
+            }
+
+            match self.state.step(self.grammar, self.table, &peek, false) {
+                Ok(true) => {
+                    self.buffer.pop_front();
+                    self.consumed += 1;
+                }
+                Ok(false) => (),
+                Err(e) => {
+                    self.error = Some(e);
+                    return;
+                }
+            }
+        }
+
+        if let Some(token) = self.buffer.front() {
+            self.error = Some(ParseError::Unexpected {
+                terminal: token.terminal.clone(),
+                expected: vec![vec![Terminal::eoi()]],
+                label: None,
+                span: token.span,
+                open: Vec::new(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Id, Key, Terminal},
+        span::{Span, Token},
+    };
+
+    use super::{PushParser, Status};
+
+    #[test]
+    fn push_1() {
+        let mut grammar = Grammar::new(Key::of::<Vec<u8>>());
+        let mut stack = Vec::new();
+
+        Vec::<u8>::generate(&mut grammar, &mut stack);
+
+        let uint = Token::new(Terminal(Key::of::<u8>()), 0..1);
+        let boolean = Token::new(Terminal(Key::of::<bool>()), 1..2);
+        let vec = Key::of::<Vec<u8>>();
+
+        let k = 2;
+        let table = grammar.table(k);
+        let mut parser = PushParser::new(&grammar, &table, k).unwrap();
+
+        assert_eq!(parser.feed(uint.clone()), Status::NeedMore);
+        assert_eq!(parser.feed(uint.clone()), Status::NeedMore);
+        assert_eq!(parser.consumed(), 1);
+
+        let mut speculative = parser.clone();
+        speculative.feed(boolean);
+        assert!(speculative.finish().is_err());
+
+        assert_eq!(
+            parser.finish().unwrap(),
+            vec![(vec.clone(), Id(0)), (vec.clone(), Id(0)), (vec, Id(1))]
+        );
+    }
+
+    #[test]
+    fn push_2() {
+        let mut grammar = Grammar::new(Key::of::<(u8, bool)>());
+        let mut stack = Vec::new();
+
+        <(u8, bool)>::generate(&mut grammar, &mut stack);
+
+        let uint = Token::new(Terminal(Key::of::<u8>()), 0..1);
+        let boolean = Token::new(Terminal(Key::of::<bool>()), 1..2);
+
+        let table = grammar.table(1);
+        let mut parser = PushParser::new(&grammar, &table, 1).unwrap();
+
+        assert_eq!(parser.feed(uint.clone()), Status::NeedMore);
+        assert_eq!(parser.feed(boolean), Status::Done);
+
+        let mut overfed = parser.clone();
+        assert!(matches!(overfed.feed(uint), Status::Error(_)));
+
+        assert_eq!(
+            parser.finish().unwrap(),
+            vec![(Key::of::<(u8, bool)>(), Id(0))]
+        );
+    }
+
+    #[test]
+    fn push_3() {
+        let mut grammar = Grammar::new(Key::of::<(u8, bool)>());
+        let mut stack = Vec::new();
+
+        <(u8, bool)>::generate(&mut grammar, &mut stack);
+
+        let uint = Token::new(Terminal(Key::of::<u8>()), 1..2);
+        let boolean = Token::new(Terminal(Key::of::<bool>()), 0..1);
+
+        let table = grammar.table(1);
+        let mut parser = PushParser::new(&grammar, &table, 1).unwrap();
+
+        let Status::Error(error) = parser.feed(boolean) else {
+            panic!("a leading bool is an error");
+        };
+        assert_eq!(error.span(), Some(Span::new(0, 1)));
+
+        // the parse stays at its first error, whatever follows
+        assert_eq!(parser.feed(uint), Status::Error(error.clone()));
+        assert_eq!(parser.finish(), Err(error));
+    }
+}
@@ -0,0 +1,173 @@
+use rand_core::RngCore;
+
+use crate::{
+    ambiguity::Shortest,
+    grammar::{cmp_words_by_name, Grammar, Terminals},
+    intern::{Sym, Word, WordSet},
+};
+
+impl Grammar {
+    /// All strings of the start symbol with at most `max_len` terminals, the shorter ones first
+    pub fn sentences(&self, max_len: usize) -> Vec<Terminals> {
+        let interned = self.interned();
+        let mut strings = vec![WordSet::default(); interned.rules.len()];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for (of, rule) in interned.rules.iter().enumerate() {
+                for (_, symbols) in rule {
+                    let mut words = WordSet::from_iter([Word::new()]);
+                    for symbol in symbols {
+                        let next = match symbol {
+                            Sym::Terminal(terminal) => {
+                                &WordSet::from_iter([Word::from_slice(&[*terminal])])
+                            }
+                            Sym::Nonterminal(nonterminal) => &strings[nonterminal.index()],
+                        };
+
+                        words = words
+                            .iter()
+                            .flat_map(|word| {
+                                next.iter()
+                                    .filter(|suffix| word.len() + suffix.len() <= max_len)
+                                    .map(move |suffix| word.iter().chain(suffix).copied().collect())
+                            })
+                            .collect();
+                    }
+
+                    for word in words {
+                        changed |= strings[of].insert(word);
+                    }
+                }
+            }
+        }
+
+        let start = interned.nonterminal_id(&self.start);
+        let mut sentences = strings[start.index()]
+            .iter()
+            .map(|word| interned.resolve(word))
+            .collect::<Vec<_>>();
+        sentences.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| cmp_words_by_name(a, b)));
+        sentences
+    }
+
+    /// A random string of the start symbol. Below `depth_limit` the productions are picked
+    /// uniformly, deeper down the ones towards the shortest strings, so that the derivation ends.
+    /// Returns `None` if the start symbol derives no string at all
+    pub fn random_sentence<R: RngCore + ?Sized>(
+        &self,
+        rng: &mut R,
+        depth_limit: usize,
+    ) -> Option<Terminals> {
+        let interned = self.interned();
+        let shortest = Shortest::new(interned);
+
+        let start = interned.nonterminal_id(&self.start);
+        shortest.production(start)?;
+
+        let mut terminals = Vec::new();
+        let mut pending = vec![(Sym::Nonterminal(start), 0)];
+
+        while let Some((symbol, depth)) = pending.pop() {
+            let nonterminal = match symbol {
+                Sym::Terminal(terminal) => {
+                    terminals.push(terminal);
+                    continue;
+                }
+                Sym::Nonterminal(nonterminal) => nonterminal,
+            };
+
+            let rule = &interned.rules[nonterminal.index()];
+            let index = match depth < depth_limit {
+                true => {
+                    // productions with a symbol that derives nothing would never end
+                    let productive = (0..rule.len())
+                        .filter(|index| {
+                            rule[*index].1.iter().all(|symbol| match symbol {
+                                Sym::Terminal(_) => true,
+                                Sym::Nonterminal(nonterminal) => {
+                                    shortest.production(*nonterminal).is_some()
+                                }
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    productive[rng.next_u64() as usize % productive.len()]
+                }
+                false => shortest.production(nonterminal).unwrap(),
+            };
+
+            pending.extend(
+                rule[index]
+                    .1
+                    .iter()
+                    .rev()
+                    .map(|symbol| (*symbol, depth + 1)),
+            );
+        }
+
+        Some(interned.resolve(&terminals))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Key, Terminal},
+        test_util::XorShift,
+    };
+
+    #[test]
+    fn sentences_1() {
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let boolean = Terminal(Key::of::<bool>());
+
+        let sentences = grammar.sentences(2);
+        assert_eq!(sentences.len(), 4);
+        assert_eq!(sentences[0], vec![]);
+        assert_eq!(sentences[1], vec![uint.clone()]);
+        assert!(sentences.contains(&vec![uint.clone(), uint.clone()]));
+        assert!(sentences.contains(&vec![uint, boolean]));
+
+        // the strings of length n are those of n - 1 followed by `u8` and
+        // those of n - 2 followed by `u8 bool`, so 1 + 1 + 2 + 3 + 5 of them
+        let sentences = grammar.sentences(4);
+        assert_eq!(sentences.len(), 12);
+        assert!(sentences
+            .windows(2)
+            .all(|pair| pair[0].len() <= pair[1].len()));
+        for sentence in &sentences {
+            assert!(grammar.parse(1, sentence).is_ok());
+        }
+    }
+
+    #[test]
+    fn random_sentence_1() {
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        let mut lengths = Vec::new();
+        for _ in 0..100 {
+            let sentence = grammar.random_sentence(&mut rng, 8).unwrap();
+            assert!(grammar.parse(1, &sentence).is_ok());
+            lengths.push(sentence.len());
+        }
+
+        assert!(lengths.contains(&0));
+        assert!(lengths.iter().any(|len| *len > 1));
+
+        // the vector ends as soon as the depth limit is reached
+        assert_eq!(grammar.random_sentence(&mut rng, 0), Some(vec![]));
+    }
+}
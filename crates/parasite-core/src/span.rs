@@ -0,0 +1,174 @@
+use core::fmt;
+use std::ops::Range;
+
+use crate::grammar::Terminal;
+
+/// A byte range in the source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn empty(at: usize) -> Self {
+        Self::new(at, at)
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The smallest span covering both spans
+    pub fn join(self, other: Span) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(value: Range<usize>) -> Self {
+        Self::new(value.start, value.end)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// A terminal of the input together with its position in the source
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token {
+    pub terminal: Terminal,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new(terminal: Terminal, span: impl Into<Span>) -> Self {
+        Self {
+            terminal,
+            span: span.into(),
+        }
+    }
+
+    /// Input without a source gets the index of each terminal as its span
+    pub fn indexed(terminals: &[Terminal]) -> impl Iterator<Item = Token> + '_ {
+        terminals
+            .iter()
+            .enumerate()
+            .map(|(i, terminal)| Token::new(terminal.clone(), i..i + 1))
+    }
+}
+
+/// A 1-based line and column, the column counted in chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Resolves byte offsets of spans into lines and columns of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    source: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn len(&self) -> usize {
+        self.source.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.source[self.line_starts[line]..offset].chars().count() + 1;
+
+        Location {
+            line: line + 1,
+            column,
+        }
+    }
+
+    /// The text of the 1-based `line` without its line break
+    pub fn line(&self, line: usize) -> &str {
+        let span = self.line_span(line);
+        &self.source[span.range()]
+    }
+
+    pub fn line_span(&self, line: usize) -> Span {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+        let end = if self.source[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+
+        Span::new(start, end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Location, SourceMap, Span};
+
+    #[test]
+    fn source_map_1() {
+        let map = SourceMap::new("let a = 1;\nlet bé = 2;\r\n\nend");
+
+        assert_eq!(map.location(0), Location { line: 1, column: 1 });
+        assert_eq!(map.location(4), Location { line: 1, column: 5 });
+        assert_eq!(map.location(11), Location { line: 2, column: 1 });
+        assert_eq!(map.location(18), Location { line: 2, column: 7 });
+        assert_eq!(map.location(26), Location { line: 4, column: 1 });
+
+        assert_eq!(map.line(1), "let a = 1;");
+        assert_eq!(map.line(2), "let bé = 2;");
+        assert_eq!(map.line(3), "");
+        assert_eq!(map.line(4), "end");
+        assert_eq!(map.line_span(4), Span::new(26, 29));
+    }
+}
@@ -1,39 +1,71 @@
 use owo_colors::OwoColorize;
 
-use crate::grammar::{Grammar, Id, Key, Terminals};
+use crate::{
+    first::concat_k,
+    grammar::{Grammar, Id, Key, Terminals},
+    intern::{Interned, Word},
+};
 use core::fmt;
+use rustc_hash::FxHashMap;
 use std::{collections::HashMap, ops::Index};
 
 impl Grammar {
     pub fn table(&self, k: usize) -> Table {
-        let first_table = self.first_k(k);
-        println!("{first_table}");
-        let follow_sets = self.follow_k(k, &first_table);
+        self.table_from(k, &self.start)
+    }
 
+    /// Builds the table so that `entry` may be followed by the end of the input
+    pub fn table_from(&self, k: usize, entry: &Key) -> Table {
+        let interned = self.interned();
         let mut table = Table::new();
 
-        for key in self.keys() {
-            let mut row = Row::new();
-            let first_sets = &first_table[&key];
-
-            for (id, first_set) in first_sets {
-                for first_item in first_set {
-                    if first_item.is_empty() {
-                        for follow_item in &follow_sets[&key] {
-                            row.insert(follow_item.clone(), *id);
-                        }
-                    } else {
-                        row.insert(first_item.clone(), *id);
-                    }
-                }
-            }
-            table.insert(key, row);
+        for (key, cells) in interned.nonterminals.iter().zip(interned.cells(k, entry)) {
+            // of competing productions, the last one wins
+            let row = cells
+                .into_iter()
+                .map(|(word, ids)| (interned.resolve(&word), *ids.last().unwrap()))
+                .collect();
+            table.insert(key.clone(), row);
         }
 
         table
     }
 }
 
+impl Interned {
+    /// The productions of each nonterminal for each lookahead, in the order of their ids
+    pub(crate) fn cells(&self, k: usize, entry: &Key) -> Vec<FxHashMap<Word, Vec<Id>>> {
+        let first = self.first_k(k);
+        let joined = first
+            .iter()
+            .map(|rule| rule.iter().flat_map(|(_, words)| words).cloned().collect())
+            .collect::<Vec<_>>();
+        let follow = self.follow_k(k, &joined, self.nonterminal_id(entry));
+
+        first
+            .iter()
+            .zip(&follow)
+            .map(|(first_sets, follow_set)| {
+                let mut cells = FxHashMap::<Word, Vec<Id>>::default();
+                let mut insert = |word: &Word, id| {
+                    let ids = cells.entry(word.clone()).or_default();
+                    if !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                };
+
+                // items shorter than k, the empty one included, continue with FOLLOW_k
+                for (id, first_set) in first_sets {
+                    for word in concat_k(first_set, follow_set, k) {
+                        insert(&word, *id);
+                    }
+                }
+                cells
+            })
+            .collect()
+    }
+}
+
 pub type Row = HashMap<Terminals, Id>;
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -47,6 +79,10 @@ impl Table {
     pub fn insert(&mut self, key: Key, row: Row) -> Option<Row> {
         self.0.insert(key, row)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Row)> {
+        self.0.iter()
+    }
 }
 
 impl fmt::Display for Table {
@@ -178,3 +214,35 @@ impl Index<&Key> for Table {
 //         panic!();
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        combinators::{Just, SeparatedBy},
+        grammar::{Grammar, Key, Terminal},
+    };
+
+    #[test]
+    fn table_k_2() {
+        // the last element of the list is only told apart by what follows the list
+        type List = (SeparatedBy<Just<','>, u8>, bool);
+
+        let mut grammar = Grammar::new(Key::of::<List>());
+        let mut stack = Vec::new();
+        List::generate(&mut grammar, &mut stack);
+
+        let table = grammar.table(2);
+        let row = &table[&Key::of::<SeparatedBy<Just<','>, u8>>()];
+        assert!(row.keys().all(|terminals| terminals.len() == 2));
+
+        let terminals = [
+            Terminal::from(Key::of::<u8>()),
+            Terminal::from(Key::of::<Just<','>>()),
+            Terminal::from(Key::of::<u8>()),
+            Terminal::from(Key::of::<bool>()),
+        ];
+        assert!(grammar.parse(2, &terminals).is_ok());
+    }
+}
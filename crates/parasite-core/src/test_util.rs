@@ -0,0 +1,91 @@
+use rand_core::RngCore;
+
+use crate::grammar::{Grammar, Id, Key, Rule, Symbol, Terminal};
+
+/// Builds a grammar from rules written as names, lowercase names are terminals
+pub fn grammar(rules: &[(&str, &[&[&str]])]) -> Grammar {
+    let mut grammar = Grammar::new(Key::new(rules[0].0));
+
+    for (key, productions) in rules {
+        let mut rule = Rule::new();
+        for (id, symbols) in productions.iter().enumerate() {
+            let symbols = symbols
+                .iter()
+                .map(|name| match name.starts_with(char::is_lowercase) {
+                    true => Symbol::terminal(Key::new(*name)),
+                    false => Symbol::nonterminal(Key::new(*name)),
+                })
+                .collect::<Vec<_>>();
+            match symbols.is_empty() {
+                true => rule.insert(Id(id), vec![Symbol::Epsilon]),
+                false => rule.insert(Id(id), symbols),
+            };
+        }
+        grammar.insert(Key::new(*key), rule);
+    }
+
+    grammar
+}
+
+pub fn terminals(names: &[&str]) -> Vec<Terminal> {
+    names
+        .iter()
+        .map(|name| Terminal::from(Key::new(*name)))
+        .collect()
+}
+
+/// A xorshift generator, so that every seed of a randomised test is reproducible
+pub struct XorShift(pub u64);
+
+impl RngCore for XorShift {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        for byte in dst {
+            *byte = self.next_u64() as u8;
+        }
+    }
+}
+
+/// A small grammar over nonterminals `N0..N3` and terminals `a`, `b`,
+/// drawn from a xorshift generator so that every seed is reproducible
+pub fn random_grammar(seed: u64) -> Grammar {
+    let mut rng = XorShift(seed);
+    let mut next = |bound: u64| rng.next_u64() % bound;
+
+    let nonterminals = (0..3)
+        .map(|n| Key::new(format!("N{n}")))
+        .collect::<Vec<_>>();
+    let terminals = ["a", "b"].map(Key::new);
+
+    let mut grammar = Grammar::new(nonterminals[0].clone());
+    for key in &nonterminals {
+        let mut rule = Rule::new();
+        for id in 0..1 + next(3) {
+            let symbols = (0..next(4))
+                .map(|_| match next(5) {
+                    0 | 1 => Symbol::terminal(terminals[next(2) as usize].clone()),
+                    _ => Symbol::nonterminal(nonterminals[next(3) as usize].clone()),
+                })
+                .collect::<Vec<_>>();
+
+            if symbols.is_empty() {
+                rule.insert(Id(id as usize), vec![Symbol::Epsilon]);
+            } else {
+                rule.insert(Id(id as usize), symbols);
+            }
+        }
+        grammar.insert(key.clone(), rule);
+    }
+
+    grammar
+}
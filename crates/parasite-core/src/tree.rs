@@ -0,0 +1,293 @@
+use core::fmt;
+
+use owo_colors::OwoColorize;
+
+use crate::{
+    grammar::{Grammar, Id, Key, Symbol, Terminal},
+    span::{Span, Token},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    pub key: Key,
+    pub id: Id,
+    pub span: Span,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Tree(Tree),
+    Token(Token),
+}
+
+impl Node {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Tree(tree) => tree.span,
+            Self::Token(token) => token.span,
+        }
+    }
+}
+
+impl Tree {
+    /// The applied productions in the order of a leftmost derivation
+    pub fn derivation(&self) -> Vec<(Key, Id)> {
+        let mut applied = vec![(self.key.clone(), self.id)];
+
+        for child in &self.children {
+            if let Node::Tree(tree) = child {
+                applied.append(&mut tree.derivation());
+            }
+        }
+
+        applied
+    }
+
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        for child in &self.children {
+            match child {
+                Node::Tree(tree) => tokens.append(&mut tree.tokens()),
+                Node::Token(token) => tokens.push(token.clone()),
+            }
+        }
+
+        tokens
+    }
+
+    pub fn terminals(&self) -> Vec<Terminal> {
+        self.tokens()
+            .into_iter()
+            .map(|token| token.terminal)
+            .collect()
+    }
+
+    /// Replaces the tokens of the tree in order, updating the spans of all subtrees
+    pub fn respan(&mut self, tokens: &[Token]) {
+        let mut tokens = tokens.iter().cloned().peekable();
+        let mut end = 0;
+
+        self.respan_with(&mut tokens, &mut end);
+    }
+
+    fn respan_with(
+        &mut self,
+        tokens: &mut std::iter::Peekable<impl Iterator<Item = Token>>,
+        end: &mut usize,
+    ) {
+        let start = tokens.peek().map_or(*end, |token| token.span.start);
+
+        for child in &mut self.children {
+            match child {
+                Node::Tree(tree) => tree.respan_with(tokens, end),
+                Node::Token(token) => {
+                    let next = tokens.next().expect("tokens ended early");
+                    assert_eq!(next.terminal, token.terminal);
+                    *end = next.span.end;
+                    *token = next;
+                }
+            }
+        }
+
+        self.span = span_of(&self.children, start);
+    }
+
+    /// Displays the tree with the production names of `grammar`
+    pub fn display<'a>(&'a self, grammar: &'a Grammar) -> TreeDisplay<'a> {
+        TreeDisplay {
+            tree: self,
+            grammar,
+        }
+    }
+
+    fn fmt_indented(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        depth: usize,
+        grammar: Option<&Grammar>,
+    ) -> fmt::Result {
+        let name = match grammar {
+            Some(grammar) => grammar.production_name(&self.key, self.id),
+            None => format!("{} {}", self.key, self.id),
+        };
+        writeln!(
+            f,
+            "{}{} {}",
+            "\t".repeat(depth),
+            name.italic(),
+            self.span.dimmed()
+        )?;
+
+        for child in &self.children {
+            match child {
+                Node::Tree(tree) => tree.fmt_indented(f, depth + 1, grammar)?,
+                Node::Token(token) => writeln!(
+                    f,
+                    "{}{} {}",
+                    "\t".repeat(depth + 1),
+                    token.terminal,
+                    token.span.dimmed()
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0, None)
+    }
+}
+
+pub struct TreeDisplay<'a> {
+    tree: &'a Tree,
+    grammar: &'a Grammar,
+}
+
+impl fmt::Display for TreeDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.tree.fmt_indented(f, 0, Some(self.grammar))
+    }
+}
+
+impl Grammar {
+    /// Builds the tree of the leftmost derivation `applied`, as returned by `Grammar::parse`,
+    /// over the `tokens` it was parsed from
+    pub fn tree(&self, applied: &[(Key, Id)], tokens: &[Token]) -> Tree {
+        self.tree_after(applied, tokens, 0)
+    }
+
+    /// Like [`Grammar::tree`] for `tokens` which follow a token ending at `end`
+    pub(crate) fn tree_after(
+        &self,
+        applied: &[(Key, Id)],
+        tokens: &[Token],
+        mut end: usize,
+    ) -> Tree {
+        let mut applied = applied.iter().cloned();
+        let mut tokens = tokens.iter().cloned().peekable();
+
+        self.subtree(&mut applied, &mut tokens, &mut end)
+    }
+
+    fn subtree(
+        &self,
+        applied: &mut impl Iterator<Item = (Key, Id)>,
+        tokens: &mut std::iter::Peekable<impl Iterator<Item = Token>>,
+        end: &mut usize,
+    ) -> Tree {
+        let (key, id) = applied.next().expect("derivation ended early");
+        // empty trees are placed right before the next token
+        let start = tokens.peek().map_or(*end, |token| token.span.start);
+        let mut children = Vec::new();
+
+        for symbol in &self.productions[&key][&id] {
+            match symbol {
+                Symbol::Epsilon => (),
+                Symbol::Terminal(terminal) => {
+                    let token = tokens.next().expect("tokens ended early");
+                    assert_eq!(&token.terminal, terminal);
+                    *end = token.span.end;
+                    children.push(Node::Token(token));
+                }
+                Symbol::Nonterminal(_) => {
+                    children.push(Node::Tree(self.subtree(applied, tokens, end)))
+                }
+            }
+        }
+
+        Tree {
+            key,
+            id,
+            span: span_of(&children, start),
+            children,
+        }
+    }
+}
+
+/// Empty trees are placed at `start`
+pub(crate) fn span_of(children: &[Node], start: usize) -> Span {
+    children
+        .iter()
+        .map(Node::span)
+        .reduce(Span::join)
+        .unwrap_or(Span::empty(start))
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        grammar::{Grammar, Id, Key, Production, Terminal},
+        span::{Span, Token},
+    };
+
+    use super::{Node, Tree};
+
+    #[test]
+    fn tree_1() {
+        let mut grammar = Grammar::new(Key::of::<Vec<u8>>());
+        let mut stack = Vec::new();
+
+        Vec::<u8>::generate(&mut grammar, &mut stack);
+
+        let uint = Terminal(Key::of::<u8>());
+        let vec = Key::of::<Vec<u8>>();
+
+        let token = Token::new(uint.clone(), 2..5);
+        let applied = grammar.parse(1, std::slice::from_ref(&uint)).unwrap();
+        let tree = grammar.tree(&applied, std::slice::from_ref(&token));
+
+        assert_eq!(
+            tree,
+            Tree {
+                key: vec.clone(),
+                id: Id(0),
+                span: Span::new(2, 5),
+                children: vec![
+                    Node::Token(token.clone()),
+                    Node::Tree(Tree {
+                        key: vec,
+                        id: Id(1),
+                        span: Span::empty(5),
+                        children: Vec::new(),
+                    })
+                ]
+            }
+        );
+        assert_eq!(tree.derivation(), applied);
+        assert_eq!(tree.terminals(), vec![uint]);
+        assert_eq!(tree.tokens(), vec![token]);
+    }
+
+    #[test]
+    fn tree_2() {
+        let mut grammar = Grammar::new(Key::of::<Vec<u8>>());
+        let mut stack = Vec::new();
+
+        Vec::<u8>::generate(&mut grammar, &mut stack);
+
+        let vec = Key::of::<Vec<u8>>();
+        grammar.set_production(
+            vec.clone(),
+            Id(0),
+            Production::new("Vec::Cons").with_fields([Some("head"), Some("tail")]),
+        );
+
+        let uint = Terminal(Key::of::<u8>());
+        let applied = grammar.parse(1, std::slice::from_ref(&uint)).unwrap();
+        let tree = grammar.tree(&applied, &Token::indexed(&[uint]).collect::<Vec<_>>());
+
+        assert_eq!(grammar.production_name(&vec, Id(0)), "Vec::Cons");
+        assert_eq!(grammar.production_name(&vec, Id(1)), "Vec<u8>#1");
+
+        let displayed = tree.display(&grammar).to_string();
+        assert!(displayed.contains("Vec::Cons") && displayed.contains("Vec<u8>#1"));
+        assert!(grammar.trace(&applied).to_string().contains("Vec::Cons"));
+    }
+}
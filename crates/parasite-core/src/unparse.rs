@@ -0,0 +1,558 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, LinkedList, VecDeque},
+    mem,
+    ops::Range,
+};
+
+use crate::{
+    builder::Syntactical,
+    combinators::{
+        Any, DelimitedBy, End, Identifier, Just, NewLine, NonEmptyVec, PaddedBy, Rec, SeparatedBy,
+        Spanned, WhiteSpace,
+    },
+    grammar::{Id, Key, Terminal},
+    pretty::{Doc, INDENT},
+    span::{Span, Token},
+    visit::{VisitMut, VisitorMut},
+};
+
+/// Collects the terminals of a value together with their text and
+/// the productions they were derived with, and lays them out as a [`Doc`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Unparser {
+    tokens: Vec<(Terminal, String)>,
+    applied: Vec<(Key, Id)>,
+    /// The indices of the terminals of each [`Spanned`] value, in the order they start
+    spanned: Vec<Range<usize>>,
+    /// The documents of the innermost open group or nest
+    docs: Vec<Doc>,
+}
+
+impl Unparser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emits the terminal `T` written as `text`
+    pub fn terminal<T: Syntactical + ?Sized>(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        self.docs.push(Doc::text(text.clone()));
+        self.tokens.push((Terminal::from(Key::of::<T>()), text));
+    }
+
+    /// Starts the production `id` of `T`, its symbols have to be emitted next
+    pub fn production<T: Syntactical + ?Sized>(&mut self, id: Id) {
+        self.applied.push((Key::of::<T>(), id));
+    }
+
+    /// A space, or a new line if the enclosing group is broken
+    pub fn line(&mut self) {
+        self.docs.push(Doc::Line);
+    }
+
+    /// Nothing, or a new line if the enclosing group is broken
+    pub fn softline(&mut self) {
+        self.docs.push(Doc::SoftLine);
+    }
+
+    pub fn hardline(&mut self) {
+        self.docs.push(Doc::HardLine);
+    }
+
+    /// Records the terminals `unparse` emits as the ones of a [`Spanned`] value
+    pub fn spanned(&mut self, unparse: impl FnOnce(&mut Self)) {
+        let index = self.spanned.len();
+        let start = self.tokens.len();
+        self.spanned.push(start..start);
+        unparse(self);
+        self.spanned[index].end = self.tokens.len();
+    }
+
+    /// Lays out everything `unparse` emits with `layout`
+    pub fn layout(&mut self, layout: impl FnOnce(Doc) -> Doc, unparse: impl FnOnce(&mut Self)) {
+        let outer = mem::take(&mut self.docs);
+        unparse(self);
+        let inner = mem::replace(&mut self.docs, outer);
+        self.docs.push(layout(Doc::Concat(inner)));
+    }
+
+    /// Keeps everything `unparse` emits on one line if it fits
+    pub fn group(&mut self, unparse: impl FnOnce(&mut Self)) {
+        self.layout(Doc::group, unparse);
+    }
+
+    /// Indents the new lines of everything `unparse` emits
+    pub fn nest(&mut self, indent: usize, unparse: impl FnOnce(&mut Self)) {
+        self.layout(|doc| doc.nest(indent), unparse);
+    }
+
+    pub fn terminals(&self) -> Vec<Terminal> {
+        self.tokens
+            .iter()
+            .map(|(terminal, _)| terminal.clone())
+            .collect()
+    }
+
+    /// The applied productions in the order of a leftmost derivation, like `Grammar::parse` returns them
+    pub fn applied(&self) -> &[(Key, Id)] {
+        &self.applied
+    }
+
+    /// The texts of the terminals, with a space only between those which would
+    /// otherwise run together into one word, so that the text lexes the same again
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (index, (_, token)) in self.tokens.iter().enumerate() {
+            if self.separated(index) {
+                text.push(' ');
+            }
+            text.push_str(token);
+        }
+        text
+    }
+
+    pub fn doc(&self) -> Doc {
+        Doc::concat(self.docs.iter().cloned())
+    }
+
+    /// The text laid out to fit in `width` columns where possible
+    pub fn pretty(&self, width: usize) -> String {
+        self.doc().render(width)
+    }
+
+    /// The terminals with their spans in [`Unparser::text`]
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut start = 0;
+        self.tokens
+            .iter()
+            .enumerate()
+            .map(|(index, (terminal, text))| {
+                if self.separated(index) {
+                    start += 1;
+                }
+                let span = Span::new(start, start + text.len());
+                start = span.end;
+                Token::new(terminal.clone(), span)
+            })
+            .collect()
+    }
+
+    /// Whether the terminal at `index` and the one before both border on word characters
+    fn separated(&self, index: usize) -> bool {
+        let word = |c: char| c.is_alphanumeric() || c == '_';
+        index > 0
+            && self.tokens[index - 1].1.ends_with(word)
+            && self.tokens[index].1.starts_with(word)
+    }
+}
+
+/// Writes a value back as the terminals of its grammar, following the productions of [`Syntactical`]
+pub trait Unparse: Syntactical {
+    fn unparse(&self, unparser: &mut Unparser);
+
+    fn to_unparser(&self) -> Unparser {
+        let mut unparser = Unparser::new();
+        self.unparse(&mut unparser);
+        unparser
+    }
+
+    /// Formats the value with the layouts of its [`Unparse`] impls
+    fn pretty(&self, width: usize) -> String {
+        self.to_unparser().pretty(width)
+    }
+
+    /// Sets the span of every [`Spanned`] value inside to the span of its terminals in
+    /// `tokens`, e.g. the tokens of the tree the value was parsed from.
+    /// Panics unless `tokens` hold the terminals of the value
+    fn respan(&mut self, tokens: &[Token])
+    where
+        Self: VisitMut + Sized,
+    {
+        let unparser = self.to_unparser();
+        assert!(
+            unparser
+                .terminals()
+                .iter()
+                .eq(tokens.iter().map(|token| &token.terminal)),
+            "the tokens are not the terminals of the value"
+        );
+
+        let spans = unparser
+            .spanned
+            .iter()
+            .map(|range| match &tokens[range.clone()] {
+                [] => match tokens.get(range.start) {
+                    Some(next) => Span::empty(next.span.start),
+                    None => Span::empty(tokens.last().map_or(0, |last| last.span.end)),
+                },
+                [first, ..] => first.span.join(tokens[range.end - 1].span),
+            });
+        self.visit_mut(&mut Respan(spans));
+    }
+}
+
+/// Sets the spans of [`Spanned`] values in the order they are entered
+struct Respan<I>(I);
+
+impl<I: Iterator<Item = Span>> VisitorMut for Respan<I> {
+    fn enter<T: VisitMut>(&mut self, node: &mut T) {
+        if let Some(span) = node.span_mut() {
+            *span = self.0.next().expect("every Spanned value was unparsed");
+        }
+    }
+}
+
+/// Unparses the elements of a collection `C` with the productions `C -> T C | ε`
+fn collection<'a, C, T>(unparser: &mut Unparser, elements: impl IntoIterator<Item = &'a T>)
+where
+    C: Syntactical,
+    T: Unparse + 'a,
+{
+    for element in elements {
+        unparser.production::<C>(Id(0));
+        element.unparse(unparser);
+    }
+    unparser.production::<C>(Id(1));
+}
+
+/// Unparses the entries of a map `C` with the productions `C -> K V C | ε`
+fn map<'a, C, K, V>(unparser: &mut Unparser, entries: impl IntoIterator<Item = (&'a K, &'a V)>)
+where
+    C: Syntactical,
+    K: Unparse + 'a,
+    V: Unparse + 'a,
+{
+    for (key, value) in entries {
+        unparser.production::<C>(Id(0));
+        key.unparse(unparser);
+        value.unparse(unparser);
+    }
+    unparser.production::<C>(Id(1));
+}
+
+impl<T: Unparse> Unparse for Box<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        (**self).unparse(unparser);
+    }
+}
+
+impl<T: Unparse> Unparse for Option<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        match self {
+            Some(value) => {
+                unparser.production::<Self>(Id(0));
+                value.unparse(unparser);
+            }
+            None => unparser.production::<Self>(Id(1)),
+        }
+    }
+}
+
+impl<T: Unparse, const N: usize> Unparse for [T; N] {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        for element in self {
+            element.unparse(unparser);
+        }
+    }
+}
+
+impl<T: Unparse> Unparse for Vec<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        collection::<Self, T>(unparser, self);
+    }
+}
+
+impl<T: Unparse> Unparse for VecDeque<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        collection::<Self, T>(unparser, self);
+    }
+}
+
+impl<T: Unparse> Unparse for LinkedList<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        collection::<Self, T>(unparser, self);
+    }
+}
+
+impl<T: Unparse> Unparse for HashSet<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        collection::<Self, T>(unparser, self);
+    }
+}
+
+impl<T: Unparse> Unparse for BTreeSet<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        collection::<Self, T>(unparser, self);
+    }
+}
+
+impl<K: Unparse, V: Unparse> Unparse for HashMap<K, V> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        map::<Self, K, V>(unparser, self);
+    }
+}
+
+impl<K: Unparse, V: Unparse> Unparse for BTreeMap<K, V> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        map::<Self, K, V>(unparser, self);
+    }
+}
+
+impl Unparse for () {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.terminal::<Self>("");
+    }
+}
+
+impl<T: Unparse, U: Unparse> Unparse for (T, U) {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        self.0.unparse(unparser);
+        self.1.unparse(unparser);
+    }
+}
+
+impl<T: Unparse, U: Unparse, V: Unparse> Unparse for (T, U, V) {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        self.0.unparse(unparser);
+        self.1.unparse(unparser);
+        self.2.unparse(unparser);
+    }
+}
+
+impl<T: Unparse, U: Unparse, V: Unparse, W: Unparse> Unparse for (T, U, V, W) {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        self.0.unparse(unparser);
+        self.1.unparse(unparser);
+        self.2.unparse(unparser);
+        self.3.unparse(unparser);
+    }
+}
+
+macro_rules! unparse_to_string {
+    ($($ty:ty),*) => {
+        $(
+            impl Unparse for $ty {
+                fn unparse(&self, unparser: &mut Unparser) {
+                    unparser.terminal::<Self>(self.to_string());
+                }
+            }
+        )*
+    };
+}
+
+unparse_to_string!(
+    String, char, bool, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64
+);
+
+impl<T: Unparse> Unparse for Rec<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        unparser.nest(INDENT, |unparser| (*self.0).unparse(unparser));
+    }
+}
+
+impl<T: Unparse> Unparse for NonEmptyVec<T> {
+    /// Panics if the vector is empty after all
+    fn unparse(&self, unparser: &mut Unparser) {
+        let (first, rest) = self.0.split_first().expect("NonEmptyVec is empty");
+
+        unparser.production::<Self>(Id(0));
+        first.unparse(unparser);
+        collection::<Vec<T>, T>(unparser, rest);
+    }
+}
+
+impl<const CHAR: char> Unparse for Just<CHAR> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.terminal::<Self>(CHAR);
+    }
+}
+
+impl<S: Unparse + Default, T: Unparse> Unparse for SeparatedBy<S, T> {
+    /// Panics if there are no values, as the grammar needs at least one
+    fn unparse(&self, unparser: &mut Unparser) {
+        let (last, values) = self.0.split_last().expect("SeparatedBy is empty");
+
+        unparser.group(|unparser| {
+            for value in values {
+                unparser.production::<Self>(Id(0));
+                value.unparse(unparser);
+                S::default().unparse(unparser);
+                unparser.line();
+            }
+            unparser.production::<Self>(Id(1));
+            last.unparse(unparser);
+        });
+    }
+}
+
+impl<P: Unparse + Default, T: Unparse> Unparse for PaddedBy<P, T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        P::default().unparse(unparser);
+        self.0.unparse(unparser);
+        P::default().unparse(unparser);
+    }
+}
+
+impl<L: Unparse + Default, R: Unparse + Default, T: Unparse> Unparse for DelimitedBy<L, R, T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.production::<Self>(Id(0));
+        unparser.group(|unparser| {
+            L::default().unparse(unparser);
+            unparser.nest(INDENT, |unparser| {
+                unparser.softline();
+                self.0.unparse(unparser);
+            });
+            unparser.softline();
+            R::default().unparse(unparser);
+        });
+    }
+}
+
+impl Unparse for End {
+    fn unparse(&self, _unparser: &mut Unparser) {}
+}
+
+impl Unparse for Any {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.terminal::<Self>(self.0);
+    }
+}
+
+impl Unparse for NewLine {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.terminal::<Self>("\n");
+    }
+}
+
+impl Unparse for WhiteSpace {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.terminal::<Self>(" ");
+    }
+}
+
+impl Unparse for Identifier {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.terminal::<Self>(self.0.clone());
+    }
+}
+
+impl<T: Unparse> Unparse for Spanned<T> {
+    fn unparse(&self, unparser: &mut Unparser) {
+        unparser.spanned(|unparser| self.value.unparse(unparser));
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        builder::Syntactical,
+        combinators::{DelimitedBy, Identifier, Just, NonEmptyVec, SeparatedBy, Spanned},
+        grammar::{Grammar, Key, Terminal},
+        span::{Span, Token},
+    };
+
+    use super::Unparse;
+
+    #[test]
+    fn unparse_1() {
+        let value = vec![(1u8, Some(true)), (2, None), (3, Some(false))];
+
+        let mut grammar = Grammar::new(Key::of::<Vec<(u8, Option<bool>)>>());
+        let mut stack = Vec::new();
+        Vec::<(u8, Option<bool>)>::generate(&mut grammar, &mut stack);
+
+        let unparser = value.to_unparser();
+        assert_eq!(unparser.text(), "1 true 2 3 false");
+        assert_eq!(
+            grammar.parse(1, &unparser.terminals()).unwrap(),
+            unparser.applied()
+        );
+    }
+
+    #[test]
+    fn unparse_2() {
+        type Args = DelimitedBy<Just<'('>, Just<')'>, SeparatedBy<Just<','>, Identifier>>;
+        type Call = (Identifier, NonEmptyVec<Args>);
+
+        let args = |names: &[&str]| {
+            Args::new(SeparatedBy::new(
+                names
+                    .iter()
+                    .map(|name| Identifier(name.to_string()))
+                    .collect(),
+            ))
+        };
+        let value: Call = (
+            Identifier("call".to_string()),
+            NonEmptyVec(vec![args(&["alpha", "beta", "gamma"]), args(&["d"])]),
+        );
+
+        let mut grammar = Grammar::new(Key::of::<Call>());
+        let mut stack = Vec::new();
+        Call::generate(&mut grammar, &mut stack);
+
+        let unparser = value.to_unparser();
+        assert_eq!(unparser.text(), "call(alpha,beta,gamma)(d)");
+
+        let applied = grammar.parse(2, &unparser.terminals()).unwrap();
+        assert_eq!(applied, unparser.applied());
+
+        let tree = grammar.tree(&applied, &unparser.tokens());
+        assert_eq!(tree.span.range(), 0..unparser.text().len());
+
+        assert_eq!(value.pretty(80), "call(alpha, beta, gamma)(d)");
+        assert_eq!(value.pretty(23), "call(\n    alpha, beta, gamma\n)(d)");
+        assert_eq!(
+            value.pretty(10),
+            "call(\n    alpha,\n    beta,\n    gamma\n)(d)"
+        );
+    }
+
+    #[test]
+    fn unparse_3() {
+        type Pair = (
+            Spanned<u8>,
+            Spanned<Option<bool>>,
+            Spanned<Vec<Spanned<u8>>>,
+        );
+
+        fn spanned<T>(value: T) -> Spanned<T> {
+            Spanned::new(value, Span::default())
+        }
+
+        let mut value: Pair = (
+            spanned(1),
+            spanned(None),
+            spanned(vec![spanned(2), spanned(3)]),
+        );
+
+        let mut grammar = Grammar::new(Key::of::<Pair>());
+        let mut stack = Vec::new();
+        Pair::generate(&mut grammar, &mut stack);
+
+        // the tokens of `1\n  2 3` as a lexer would find them
+        let uint = Terminal(Key::of::<u8>());
+        let tokens = [0..1, 4..5, 6..7].map(|span| Token::new(uint.clone(), span));
+        let terminals = tokens
+            .iter()
+            .map(|token| token.terminal.clone())
+            .collect::<Vec<_>>();
+        let applied = grammar.parse(1, &terminals).unwrap();
+        let tree = grammar.tree(&applied, &tokens);
+
+        value.respan(&tree.tokens());
+        assert_eq!(value.0.span, Span::new(0, 1));
+        // nothing is in between, so the span is empty before the next token
+        assert_eq!(value.1.span, Span::empty(4));
+        assert_eq!(value.2.span, Span::new(4, 7));
+        assert_eq!(value.2.value[1].span, Span::new(6, 7));
+    }
+}
@@ -0,0 +1,626 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, LinkedList, VecDeque},
+    hash::Hash,
+    mem,
+};
+
+use crate::{
+    builder::Syntactical,
+    combinators::{
+        Any as AnyChar, DelimitedBy, End, Identifier, Just, NewLine, NonEmptyVec, PaddedBy, Rec,
+        SeparatedBy, Spanned, WhiteSpace,
+    },
+    span::Span,
+};
+
+/// Called before and after the children of every node,
+/// nodes of a certain type are found with [`Any::downcast_ref`]
+pub trait Visitor {
+    fn enter<T: Visit>(&mut self, _node: &T) {}
+
+    fn exit<T: Visit>(&mut self, _node: &T) {}
+}
+
+/// Walks a value and its children in the order of their terminals
+pub trait Visit: Syntactical {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V);
+}
+
+/// Like [`Visitor`], the children are visited after `enter` changed the node
+pub trait VisitorMut {
+    fn enter<T: VisitMut>(&mut self, _node: &mut T) {}
+
+    fn exit<T: VisitMut>(&mut self, _node: &mut T) {}
+}
+
+pub trait VisitMut: Syntactical {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V);
+
+    /// The span of a [`Spanned`] value, so that visitors can set it whatever the type inside
+    fn span_mut(&mut self) -> Option<&mut Span> {
+        None
+    }
+}
+
+/// Rebuilds a value, `fold` is called for every node and by default only folds its children
+pub trait Folder {
+    fn fold<T: Fold>(&mut self, node: T) -> T {
+        node.fold_children(self)
+    }
+}
+
+pub trait Fold: Syntactical + Sized {
+    /// Rebuilds the value from its folded children
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self;
+}
+
+/// Applies `fold` if `node` is an `U`, for folders which only change some of the types
+pub fn fold_as<T: 'static, U: 'static>(node: T, fold: impl FnOnce(U) -> U) -> T {
+    let mut node = Some(node);
+    if let Some(slot) = (&mut node as &mut dyn Any).downcast_mut::<Option<U>>() {
+        *slot = slot.take().map(fold);
+    }
+    node.unwrap()
+}
+
+macro_rules! leaf {
+    ($($ty:ty),*) => {
+        $(
+            impl Visit for $ty {
+                fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+                    visitor.enter(self);
+                    visitor.exit(self);
+                }
+            }
+
+            impl VisitMut for $ty {
+                fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+                    visitor.enter(self);
+                    visitor.exit(self);
+                }
+            }
+
+            impl Fold for $ty {
+                fn fold_children<F: Folder + ?Sized>(self, _folder: &mut F) -> Self {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+leaf!(
+    (),
+    String,
+    char,
+    bool,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    End,
+    AnyChar,
+    NewLine,
+    WhiteSpace,
+    Identifier
+);
+
+impl<const CHAR: char> Visit for Just<CHAR> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        visitor.exit(self);
+    }
+}
+
+impl<const CHAR: char> VisitMut for Just<CHAR> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        visitor.exit(self);
+    }
+}
+
+impl<const CHAR: char> Fold for Just<CHAR> {
+    fn fold_children<F: Folder + ?Sized>(self, _folder: &mut F) -> Self {
+        self
+    }
+}
+
+/// Containers whose elements can be changed in place
+macro_rules! sequence {
+    ($($ty:ident),*) => {
+        $(
+            impl<T: Visit> Visit for $ty<T> {
+                fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+                    visitor.enter(self);
+                    self.iter().for_each(|element| element.visit(visitor));
+                    visitor.exit(self);
+                }
+            }
+
+            impl<T: VisitMut> VisitMut for $ty<T> {
+                fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+                    visitor.enter(self);
+                    self.iter_mut().for_each(|element| element.visit_mut(visitor));
+                    visitor.exit(self);
+                }
+            }
+
+            impl<T: Fold> Fold for $ty<T> {
+                fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+                    self.into_iter().map(|element| folder.fold(element)).collect()
+                }
+            }
+        )*
+    };
+}
+
+sequence!(Vec, VecDeque, LinkedList);
+
+impl<T: Visit> Visit for Box<T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        (**self).visit(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut> VisitMut for Box<T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        (**self).visit_mut(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<T: Fold> Fold for Box<T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        Box::new(folder.fold(*self))
+    }
+}
+
+impl<T: Visit> Visit for Option<T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        if let Some(value) = self {
+            value.visit(visitor);
+        }
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut> VisitMut for Option<T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        if let Some(value) = self {
+            value.visit_mut(visitor);
+        }
+        visitor.exit(self);
+    }
+}
+
+impl<T: Fold> Fold for Option<T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        self.map(|value| folder.fold(value))
+    }
+}
+
+impl<T: Visit, const N: usize> Visit for [T; N] {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.iter().for_each(|element| element.visit(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut, const N: usize> VisitMut for [T; N] {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        self.iter_mut()
+            .for_each(|element| element.visit_mut(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<T: Fold, const N: usize> Fold for [T; N] {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        self.map(|element| folder.fold(element))
+    }
+}
+
+impl<T: Visit> Visit for HashSet<T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.iter().for_each(|element| element.visit(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut + Eq + Hash> VisitMut for HashSet<T> {
+    /// The elements are taken out and inserted again, as changing them may change their hash
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        *self = mem::take(self)
+            .into_iter()
+            .map(|mut element| {
+                element.visit_mut(visitor);
+                element
+            })
+            .collect();
+        visitor.exit(self);
+    }
+}
+
+impl<T: Fold + Eq + Hash> Fold for HashSet<T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        self.into_iter()
+            .map(|element| folder.fold(element))
+            .collect()
+    }
+}
+
+impl<T: Visit> Visit for BTreeSet<T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.iter().for_each(|element| element.visit(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut + Ord> VisitMut for BTreeSet<T> {
+    /// The elements are taken out and inserted again, as changing them may change their order
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        *self = mem::take(self)
+            .into_iter()
+            .map(|mut element| {
+                element.visit_mut(visitor);
+                element
+            })
+            .collect();
+        visitor.exit(self);
+    }
+}
+
+impl<T: Fold + Ord> Fold for BTreeSet<T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        self.into_iter()
+            .map(|element| folder.fold(element))
+            .collect()
+    }
+}
+
+impl<K: Visit, V: Visit> Visit for HashMap<K, V> {
+    fn visit<Vr: Visitor + ?Sized>(&self, visitor: &mut Vr) {
+        visitor.enter(self);
+        for (key, value) in self {
+            key.visit(visitor);
+            value.visit(visitor);
+        }
+        visitor.exit(self);
+    }
+}
+
+impl<K: VisitMut + Eq + Hash, V: VisitMut> VisitMut for HashMap<K, V> {
+    /// The entries are taken out and inserted again, as changing the keys may change their hash
+    fn visit_mut<Vr: VisitorMut + ?Sized>(&mut self, visitor: &mut Vr) {
+        visitor.enter(self);
+        *self = mem::take(self)
+            .into_iter()
+            .map(|(mut key, mut value)| {
+                key.visit_mut(visitor);
+                value.visit_mut(visitor);
+                (key, value)
+            })
+            .collect();
+        visitor.exit(self);
+    }
+}
+
+impl<K: Fold + Eq + Hash, V: Fold> Fold for HashMap<K, V> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        self.into_iter()
+            .map(|(key, value)| (folder.fold(key), folder.fold(value)))
+            .collect()
+    }
+}
+
+impl<K: Visit, V: Visit> Visit for BTreeMap<K, V> {
+    fn visit<Vr: Visitor + ?Sized>(&self, visitor: &mut Vr) {
+        visitor.enter(self);
+        for (key, value) in self {
+            key.visit(visitor);
+            value.visit(visitor);
+        }
+        visitor.exit(self);
+    }
+}
+
+impl<K: VisitMut + Ord, V: VisitMut> VisitMut for BTreeMap<K, V> {
+    /// The entries are taken out and inserted again, as changing the keys may change their order
+    fn visit_mut<Vr: VisitorMut + ?Sized>(&mut self, visitor: &mut Vr) {
+        visitor.enter(self);
+        *self = mem::take(self)
+            .into_iter()
+            .map(|(mut key, mut value)| {
+                key.visit_mut(visitor);
+                value.visit_mut(visitor);
+                (key, value)
+            })
+            .collect();
+        visitor.exit(self);
+    }
+}
+
+impl<K: Fold + Ord, V: Fold> Fold for BTreeMap<K, V> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        self.into_iter()
+            .map(|(key, value)| (folder.fold(key), folder.fold(value)))
+            .collect()
+    }
+}
+
+macro_rules! tuple {
+    ($(($($ty:ident $index:tt),*)),*) => {
+        $(
+            impl<$($ty: Visit),*> Visit for ($($ty,)*) {
+                fn visit<Vr: Visitor + ?Sized>(&self, visitor: &mut Vr) {
+                    visitor.enter(self);
+                    $(self.$index.visit(visitor);)*
+                    visitor.exit(self);
+                }
+            }
+
+            impl<$($ty: VisitMut),*> VisitMut for ($($ty,)*) {
+                fn visit_mut<Vr: VisitorMut + ?Sized>(&mut self, visitor: &mut Vr) {
+                    visitor.enter(self);
+                    $(self.$index.visit_mut(visitor);)*
+                    visitor.exit(self);
+                }
+            }
+
+            impl<$($ty: Fold),*> Fold for ($($ty,)*) {
+                fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+                    ($(folder.fold(self.$index),)*)
+                }
+            }
+        )*
+    };
+}
+
+tuple!((T 0, U 1), (T 0, U 1, V 2), (T 0, U 1, V 2, W 3));
+
+impl<T: Visit> Visit for Rec<T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        (*self.0).visit(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut> VisitMut for Rec<T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        (*self.0).visit_mut(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<T: Fold> Fold for Rec<T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        Rec(Box::new(folder.fold(*self.0)))
+    }
+}
+
+impl<T: Visit> Visit for NonEmptyVec<T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0.iter().for_each(|element| element.visit(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut> VisitMut for NonEmptyVec<T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0
+            .iter_mut()
+            .for_each(|element| element.visit_mut(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<T: Fold> Fold for NonEmptyVec<T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        NonEmptyVec(
+            self.0
+                .into_iter()
+                .map(|element| folder.fold(element))
+                .collect(),
+        )
+    }
+}
+
+/// The separators are not stored, only the values are visited
+impl<S: Syntactical, T: Visit> Visit for SeparatedBy<S, T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0.iter().for_each(|value| value.visit(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<S: Syntactical, T: VisitMut> VisitMut for SeparatedBy<S, T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0.iter_mut().for_each(|value| value.visit_mut(visitor));
+        visitor.exit(self);
+    }
+}
+
+impl<S: Syntactical, T: Fold> Fold for SeparatedBy<S, T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        SeparatedBy::new(self.0.into_iter().map(|value| folder.fold(value)).collect())
+    }
+}
+
+impl<P: Syntactical, T: Visit> Visit for PaddedBy<P, T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0.visit(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<P: Syntactical, T: VisitMut> VisitMut for PaddedBy<P, T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0.visit_mut(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<P: Syntactical, T: Fold> Fold for PaddedBy<P, T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        PaddedBy::new(folder.fold(self.0))
+    }
+}
+
+impl<L: Syntactical, R: Syntactical, T: Visit> Visit for DelimitedBy<L, R, T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0.visit(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<L: Syntactical, R: Syntactical, T: VisitMut> VisitMut for DelimitedBy<L, R, T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        self.0.visit_mut(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<L: Syntactical, R: Syntactical, T: Fold> Fold for DelimitedBy<L, R, T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        DelimitedBy::new(folder.fold(self.0))
+    }
+}
+
+impl<T: Visit> Visit for Spanned<T> {
+    fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.enter(self);
+        self.value.visit(visitor);
+        visitor.exit(self);
+    }
+}
+
+impl<T: VisitMut> VisitMut for Spanned<T> {
+    fn visit_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        visitor.enter(self);
+        self.value.visit_mut(visitor);
+        visitor.exit(self);
+    }
+
+    fn span_mut(&mut self) -> Option<&mut Span> {
+        Some(&mut self.span)
+    }
+}
+
+impl<T: Fold> Fold for Spanned<T> {
+    fn fold_children<F: Folder + ?Sized>(self, folder: &mut F) -> Self {
+        Spanned::new(folder.fold(self.value), self.span)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::any::Any;
+
+    use crate::combinators::{DelimitedBy, Identifier, Just, Rec, SeparatedBy};
+
+    use super::{fold_as, Fold, Folder, Visit, VisitMut, Visitor, VisitorMut};
+
+    type Args = DelimitedBy<Just<'('>, Just<')'>, SeparatedBy<Just<','>, Identifier>>;
+    type Calls = Vec<(Identifier, Option<Rec<Args>>)>;
+
+    fn identifiers(names: &[&str]) -> Vec<Identifier> {
+        names
+            .iter()
+            .map(|name| Identifier(name.to_string()))
+            .collect()
+    }
+
+    fn calls() -> Calls {
+        vec![
+            (
+                Identifier("f".to_string()),
+                Some(Rec(Box::new(DelimitedBy::new(SeparatedBy::new(
+                    identifiers(&["a", "b"]),
+                ))))),
+            ),
+            (Identifier("g".to_string()), None),
+        ]
+    }
+
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl Visitor for Names {
+        fn enter<T: Visit>(&mut self, node: &T) {
+            if let Some(Identifier(name)) = (node as &dyn Any).downcast_ref() {
+                self.0.push(name.clone());
+            }
+        }
+    }
+
+    struct Upper;
+
+    impl VisitorMut for Upper {
+        fn exit<T: VisitMut>(&mut self, node: &mut T) {
+            if let Some(Identifier(name)) = (node as &mut dyn Any).downcast_mut() {
+                *name = name.to_uppercase();
+            }
+        }
+    }
+
+    struct Rename;
+
+    impl Folder for Rename {
+        fn fold<T: Fold>(&mut self, node: T) -> T {
+            let node = node.fold_children(self);
+            fold_as(node, |Identifier(name)| Identifier(format!("{name}'")))
+        }
+    }
+
+    #[test]
+    fn visit_1() {
+        let mut calls = calls();
+
+        let mut names = Names::default();
+        calls.visit(&mut names);
+        assert_eq!(names.0, ["f", "a", "b", "g"]);
+
+        calls.visit_mut(&mut Upper);
+        let mut names = Names::default();
+        calls.visit(&mut names);
+        assert_eq!(names.0, ["F", "A", "B", "G"]);
+
+        let calls = Rename.fold(calls);
+        let mut names = Names::default();
+        calls.visit(&mut names);
+        assert_eq!(names.0, ["F'", "A'", "B'", "G'"]);
+    }
+}
@@ -4,7 +4,7 @@ pub mod production;
 
 use alternation::{AlternationNode, AlternationsNode};
 use factor::FactorNode;
-use parasite_core::grammar::{Grammar, Id, Key, Symbol};
+use parasite_core::grammar::{Grammar, Id, Key, Production, Symbol};
 use production::ProductionNode;
 use quote::ToTokens;
 use std::collections::HashMap;
@@ -48,10 +48,12 @@ impl GrammarAst {
     // No expanded productions that have recursive productions or empty alternations without being annotated as such by ProductionKind
     pub fn expand(self) -> Grammar {
         let mut productions = HashMap::new();
+        let mut metadata = HashMap::new();
         let mut table = HashMap::new();
 
         if let Node::Production(production, index) = self.start_production() {
             let key = Key::new(production.lhs.clone().into_token_stream().to_string());
+            metadata.insert(key.clone(), production.lhs.to_string());
             let mut rule = HashMap::new();
             rule.insert(Id(0), Vec::new());
             productions.insert(key.clone(), rule);
@@ -80,6 +82,8 @@ impl GrammarAst {
                             index.push(i);
 
                             let prod_key = Key::new(productions.len().to_string());
+                            let label = metadata.get(&key).unwrap_or(&key.to_string()).clone();
+                            metadata.insert(prod_key.clone(), format!("{label}::{i}"));
 
                             let mut rule = HashMap::new();
                             rule.insert(Id(0), Vec::new());
@@ -175,10 +179,12 @@ impl GrammarAst {
             }
         }
 
-        Grammar {
-            productions,
-            start: Key::new(self.start.to_token_stream().to_string()),
+        let mut grammar = Grammar::new(Key::new(self.start.to_token_stream().to_string()));
+        grammar.productions = productions;
+        for (key, label) in metadata {
+            grammar.set_production(key, Id(0), Production::new(label).with_origin("grammar!"));
         }
+        grammar
     }
 
     pub fn iter(&self) -> impl Iterator<Item = Node> {
@@ -8,15 +8,22 @@ use syn::parse_macro_input;
 use crate::grammar::GrammarAst;
 use crate::module::module_check;
 use crate::syntactical::{syntactical_impl, terminal_impl};
+use crate::unparse::unparse_impl;
+use crate::visit::{traversal_impl, Traversal};
 
 mod grammar;
 mod module;
 mod syntactical;
+mod unparse;
+mod visit;
 
-#[proc_macro_derive(Syntactical)]
+#[proc_macro_derive(Syntactical, attributes(syntactical))]
 pub fn syntactical(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
-    let item_impl = syntactical_impl(input);
+    let item_impl = match syntactical_impl(input) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.into_compile_error().into(),
+    };
 
     quote!(
         #item_impl
@@ -25,10 +32,73 @@ pub fn syntactical(input: TokenStream) -> TokenStream {
     .into()
 }
 
-#[proc_macro_derive(Terminal)]
+#[proc_macro_derive(Terminal, attributes(syntactical))]
 pub fn terminal(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
-    let item_impl = terminal_impl(input.ident);
+    let item_impl = match terminal_impl(input) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    quote!(
+        #item_impl
+    )
+    .into_token_stream()
+    .into()
+}
+
+#[proc_macro_derive(Unparse, attributes(unparse))]
+pub fn unparse(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let item_impl = match unparse_impl(input) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    quote!(
+        #item_impl
+    )
+    .into_token_stream()
+    .into()
+}
+
+#[proc_macro_derive(Visit)]
+pub fn visit(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let item_impl = match traversal_impl(input, Traversal::Visit) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    quote!(
+        #item_impl
+    )
+    .into_token_stream()
+    .into()
+}
+
+#[proc_macro_derive(VisitMut)]
+pub fn visit_mut(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let item_impl = match traversal_impl(input, Traversal::VisitMut) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    quote!(
+        #item_impl
+    )
+    .into_token_stream()
+    .into()
+}
+
+#[proc_macro_derive(Fold)]
+pub fn fold(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let item_impl = match traversal_impl(input, Traversal::Fold) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.into_compile_error().into(),
+    };
 
     quote!(
         #item_impl
@@ -42,7 +112,9 @@ pub fn module(input: TokenStream) -> TokenStream {
     let module = input.clone();
     let mut module = parse_macro_input!(module as syn::ItemMod);
 
-    module_check(&mut module);
+    if let Err(err) = module_check(&mut module) {
+        return err.into_compile_error().into();
+    }
 
     module.into_token_stream().into()
 }
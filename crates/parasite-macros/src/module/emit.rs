@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use parasite_core::grammar::{Grammar, Key, Symbol};
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::ItemFn;
+
+use super::key::TypeKey;
+
+/// A `grammar` function building the populated `grammar` at runtime. The keys of the
+/// types are written as `Syntactical::key`, so the grammar equals the one
+/// `derive(Syntactical)` builds, names included. The types are written as the module
+/// writes them, `nonterminals` and `terminals` hold every type which got a key
+pub fn grammar_fn(grammar: &Grammar, nonterminals: &[TypeKey], terminals: &[TypeKey]) -> ItemFn {
+    let types = nonterminals
+        .iter()
+        .chain(terminals)
+        .map(|ty| (ty.clone().into(), ty.to_token_stream()))
+        .collect::<BTreeMap<Key, _>>();
+    let ty = |key: &Key| types[key].clone();
+    let key = |key: &Key| {
+        let ty = ty(key);
+        quote!(<#ty as parasite::builder::Syntactical>::key())
+    };
+
+    let start = key(&grammar.start);
+    let mut terminals = BTreeSet::new();
+
+    let rules = grammar
+        .productions
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(nonterminal, rule)| {
+            let productions =
+                rule.iter()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .map(|(id, symbols)| {
+                        let id = id.0;
+                        let symbols = symbols.iter().map(|symbol| match symbol {
+                            Symbol::Terminal(terminal) => {
+                                terminals.insert(terminal.0.clone());
+                                let key = key(&terminal.0);
+                                quote!(parasite::grammar::Symbol::terminal(#key))
+                            }
+                            Symbol::Nonterminal(nonterminal) => {
+                                let key = key(&nonterminal.0);
+                                quote!(parasite::grammar::Symbol::nonterminal(#key))
+                            }
+                            Symbol::Epsilon => quote!(parasite::grammar::Symbol::Epsilon),
+                        });
+                        quote!(rule.insert(parasite::grammar::Id(#id), vec![#(#symbols ,)*]);)
+                    });
+            let nonterminal = key(nonterminal);
+
+            quote!(
+                let mut rule = parasite::grammar::Rule::new();
+                #(#productions)*
+                grammar.insert(#nonterminal, rule);
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // named the way `Grammar::terminal` names them
+    let names = terminals.iter().map(|terminal| {
+        let ty = ty(terminal);
+        let key = key(terminal);
+        quote!(
+            grammar.names.insert(#key, <#ty as parasite::builder::Syntactical>::display_name());
+        )
+    });
+
+    let metadata = grammar
+        .metadata
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|((nonterminal, id), production)| {
+            let nonterminal = key(nonterminal);
+            let id = id.0;
+            let label = production.label.as_deref().unwrap_or_default();
+            let fields = production.fields.iter().map(|field| match field {
+                Some(field) => quote!(Some(#field)),
+                None => quote!(None),
+            });
+            // the origin is known as the item name, the derive prefixes its module
+            let origin = match &production.origin {
+                Some(origin) => quote!(.with_origin(concat!(module_path!(), "::", #origin))),
+                None => TokenStream::new(),
+            };
+            // the file of the module is only known where the grammar is built
+            let location = match &production.location {
+                Some((_, line)) => quote!(.with_location(file!(), #line)),
+                None => TokenStream::new(),
+            };
+            let docs = &production.docs;
+
+            quote!(
+                grammar.set_production(
+                    #nonterminal,
+                    parasite::grammar::Id(#id),
+                    parasite::grammar::Production::new(#label)
+                        .with_fields::<&str>([#(#fields ,)*])
+                        #origin
+                        #location
+                        .with_docs::<&str>([#(#docs ,)*]),
+                );
+            )
+        });
+
+    let expected = grammar
+        .expected
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .flat_map(|(nonterminal, expected)| {
+            let nonterminal = key(nonterminal);
+            let rule = expected.rule.iter().map(move |label| (quote!(None), label));
+            let productions = expected
+                .productions
+                .iter()
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .map(|(id, label)| {
+                    let id = id.0;
+                    (quote!(Some(parasite::grammar::Id(#id))), label)
+                });
+
+            rule.chain(productions)
+                .map(|(id, label)| quote!(grammar.set_expected(#nonterminal, #id, #label);))
+                .collect::<Vec<_>>()
+        });
+
+    syn::parse_quote!(
+        pub fn grammar() -> parasite::grammar::Grammar {
+            let mut grammar = parasite::grammar::Grammar::new(#start);
+            #(#rules)*
+            #(#names)*
+            #(#metadata)*
+            #(#expected)*
+            grammar
+        }
+    )
+}
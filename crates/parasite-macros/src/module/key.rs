@@ -1,6 +1,8 @@
 use parasite_core::grammar::Key;
 use quote::ToTokens;
-use syn::{Ident, Path, PathSegment, Type, TypeArray, TypePath, TypeTuple};
+use syn::{
+    GenericArgument, Ident, Path, PathArguments, PathSegment, Type, TypeArray, TypePath, TypeTuple,
+};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TypeKey {
@@ -20,6 +22,56 @@ impl TypeKey {
 
         Self::Path(TypePath { qself: None, path })
     }
+
+    /// The name `Key::of` gives the type: its type name without module paths
+    pub fn name(&self) -> String {
+        match self {
+            Self::Array(array) => type_name(&Type::Array(array.clone())),
+            Self::Tuple(tuple) => type_name(&Type::Tuple(tuple.clone())),
+            Self::Path(path) => path_name(&path.path),
+        }
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => path_name(&path.path),
+        Type::Tuple(tuple) if tuple.elems.len() == 1 => {
+            format!("({},)", type_name(&tuple.elems[0]))
+        }
+        Type::Tuple(tuple) => {
+            let elems = tuple.elems.iter().map(type_name).collect::<Vec<_>>();
+            format!("({})", elems.join(", "))
+        }
+        Type::Array(array) => format!(
+            "[{}; {}]",
+            type_name(&array.elem),
+            array.len.to_token_stream()
+        ),
+        Type::Paren(paren) => type_name(&paren.elem),
+        ty => ty.to_token_stream().to_string(),
+    }
+}
+
+fn path_name(path: &Path) -> String {
+    let Some(segment) = path.segments.last() else {
+        return String::new();
+    };
+    let mut name = segment.ident.to_string();
+
+    if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+        let arguments = arguments
+            .args
+            .iter()
+            .map(|argument| match argument {
+                GenericArgument::Type(ty) => type_name(ty),
+                argument => argument.to_token_stream().to_string(),
+            })
+            .collect::<Vec<_>>();
+        name += &format!("<{}>", arguments.join(", "));
+    }
+
+    name
 }
 
 impl ToTokens for TypeKey {
@@ -34,8 +86,7 @@ impl ToTokens for TypeKey {
 
 impl Into<Key> for TypeKey {
     fn into(self) -> Key {
-        let s = self.to_token_stream().to_string();
-        Key::new(s)
+        Key::new(self.name())
     }
 }
 
@@ -75,3 +126,43 @@ impl TryFrom<Type> for TypeKey {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::HashMap;
+
+    use parasite_core::{combinators::Just, grammar::Key};
+    use syn::{parse_quote, Type};
+
+    use super::TypeKey;
+
+    fn name(ty: Type) -> String {
+        TypeKey::try_from(ty).unwrap().name()
+    }
+
+    #[test]
+    fn name_1() {
+        assert_eq!(
+            name(parse_quote!(Vec<u8>)),
+            Key::of::<Vec<u8>>().to_string()
+        );
+        assert_eq!(
+            name(parse_quote!((u8, Box<Vec<bool>>, u8))),
+            Key::of::<(u8, Box<Vec<bool>>, u8)>().to_string()
+        );
+        assert_eq!(name(parse_quote!((u8,))), Key::of::<(u8,)>().to_string());
+        assert_eq!(
+            name(parse_quote!([u8; 3])),
+            Key::of::<[u8; 3]>().to_string()
+        );
+        assert_eq!(
+            name(parse_quote!(std::collections::HashMap<u8, bool>)),
+            Key::of::<HashMap<u8, bool>>().to_string()
+        );
+        assert_eq!(
+            name(parse_quote!(Just<'a'>)),
+            Key::of::<Just<'a'>>().to_string()
+        );
+    }
+}
@@ -1,16 +1,26 @@
 use parasite_core::grammar::Grammar;
+use quote::ToTokens;
 use std::collections::HashMap;
-use syn::{Attribute, Item, ItemMod};
+use syn::{punctuated::Punctuated, Attribute, DeriveInput, Item, ItemMod, Path, Token};
 
+use crate::syntactical::{syntactical_impl, terminal_impl};
+
+use emit::grammar_fn;
 use key::TypeKey;
 use populate::populate;
 
+pub mod emit;
 pub mod key;
 pub mod populate;
 
-pub fn module_check(input: &mut ItemMod) {
+/// Checks the grammar of the module and implements `Syntactical` for its items the same
+/// way `derive(Syntactical)` and `derive(Terminal)` do, so both share one key scheme.
+/// Items which already derive one of them keep their own impl, terminals are still
+/// marked with `#[terminal]`
+pub fn module_check(input: &mut ItemMod) -> syn::Result<()> {
     let mut start = None;
     let mut terminals = Vec::new();
+    let mut impls = Vec::new();
 
     if let Some((_, items)) = &mut input.content {
         let productions = items
@@ -25,12 +35,19 @@ pub fn module_check(input: &mut ItemMod) {
                         item_enum.attrs.remove(pos);
                     }
 
+                    let derived = derives(&item_enum.attrs);
                     if let Some(pos) = attrs_find(&item_enum.attrs, "terminal") {
                         terminals.push(key);
                         item_enum.attrs.remove(pos);
+                        if !derived {
+                            impls.push(derive_input(item).and_then(terminal_impl));
+                        }
                         return None;
                     }
 
+                    if !derived {
+                        impls.push(derive_input(item).and_then(syntactical_impl));
+                    }
                     Some((key, item.clone()))
                 }
                 Item::Struct(item_struct) => {
@@ -42,32 +59,90 @@ pub fn module_check(input: &mut ItemMod) {
                         item_struct.attrs.remove(pos);
                     }
 
+                    let derived = derives(&item_struct.attrs);
                     if let Some(pos) = attrs_find(&item_struct.attrs, "terminal") {
                         terminals.push(key);
                         item_struct.attrs.remove(pos);
+                        if !derived {
+                            impls.push(derive_input(item).and_then(terminal_impl));
+                        }
                         return None;
                     }
 
+                    if !derived {
+                        impls.push(derive_input(item).and_then(syntactical_impl));
+                    }
                     Some((key, item.clone()))
                 }
                 _ => None,
             })
             .collect::<HashMap<_, _>>();
 
+        for item in impls {
+            items.push(Item::Impl(item?));
+        }
+        items.iter_mut().for_each(strip_attrs);
+
         if let Some(start) = start {
             let mut grammar = Grammar::new(start.clone().into());
             let mut stack = Vec::new();
-            populate(start, &productions, &mut grammar, &mut stack, &terminals);
+            populate(
+                start,
+                &productions,
+                &mut grammar,
+                &mut stack,
+                &mut terminals,
+            );
 
-            println!("{grammar}");
-
-            let table = grammar.table(2);
-            println!("{table}");
+            // the visited nonterminals stay on the stack
+            items.push(Item::Fn(grammar_fn(&grammar, &stack, &terminals)));
 
             // TODO do checks so that it can be verified that grammar is valid
             // TODO make lookahead as attribute into the proc macro
         }
     }
+
+    Ok(())
+}
+
+fn derive_input(item: &Item) -> syn::Result<DeriveInput> {
+    syn::parse2(item.to_token_stream())
+}
+
+/// Removes the `#[syntactical(...)]` attributes, which are only known to the derives
+fn strip_attrs(item: &mut Item) {
+    let is_syntactical = |attr: &Attribute| attr.path().is_ident("syntactical");
+
+    match item {
+        Item::Enum(item_enum) if derives(&item_enum.attrs) => (),
+        Item::Struct(item_struct) if derives(&item_struct.attrs) => (),
+        Item::Enum(item_enum) => {
+            item_enum.attrs.retain(|attr| !is_syntactical(attr));
+            for variant in &mut item_enum.variants {
+                variant.attrs.retain(|attr| !is_syntactical(attr));
+            }
+        }
+        Item::Struct(item_struct) => item_struct.attrs.retain(|attr| !is_syntactical(attr)),
+        _ => (),
+    }
+}
+
+/// Whether the item derives `Syntactical` or `Terminal` itself
+fn derives(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .filter_map(|path| {
+            path.segments
+                .last()
+                .map(|segment| segment.ident.to_string())
+        })
+        .any(|derive| derive == "Syntactical" || derive == "Terminal")
 }
 
 fn attrs_find(attrs: &Vec<Attribute>, ident: &str) -> Option<usize> {
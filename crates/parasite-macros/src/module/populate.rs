@@ -1,16 +1,12 @@
-use parasite_core::grammar::{Grammar, Id, Rule, Symbol};
+use parasite_core::grammar::{Grammar, Id, Production, Rule, Symbol};
 use std::collections::HashMap;
 use syn::{
-    punctuated::Pair, token::Paren, Fields, GenericArgument, Item, PathArguments, PathSegment,
-    Type, TypePath, TypeTuple,
+    Attribute, Expr, ExprLit, Fields, GenericArgument, Item, Lit, PathArguments, PathSegment, Type,
+    TypeArray, TypePath, TypeTuple,
 };
 
 use super::key::TypeKey;
-
-pub const PRIMITIVES: &'static [&'static str] = &[
-    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize", "f32",
-    "f64", "String", "char", "bool",
-];
+use crate::syntactical::{docs, field_names, line, Attributes};
 
 pub const COLLECTIONS: &'static [&'static str] =
     &["Vec", "VecDeque", "HashSet", "BTreeSet", "LinkedList"];
@@ -22,8 +18,12 @@ pub fn populate(
     productions: &HashMap<TypeKey, Item>,
     grammar: &mut Grammar,
     stack: &mut Vec<TypeKey>,
-    terminals: &Vec<TypeKey>,
+    terminals: &mut Vec<TypeKey>,
 ) -> Symbol {
+    // the terminals are collected, so that their keys can be emitted as they are written
+    if is_terminal(&key, productions) && !terminals.contains(&key) {
+        terminals.push(key.clone());
+    }
     if terminals.contains(&key) {
         return Symbol::terminal(key.into());
     }
@@ -35,18 +35,47 @@ pub fn populate(
 
         let rule = if let Some(item) = productions.get(&key) {
             match item {
-                Item::Enum(enum_item) => enum_item
-                    .variants
-                    .iter()
-                    .enumerate()
-                    .map(|(id, variant)| {
-                        (
-                            Id(id),
-                            fields_symbols(&variant.fields, productions, grammar, stack, terminals),
-                        )
-                    })
-                    .collect(),
+                Item::Enum(enum_item) => {
+                    set_expected(grammar, &key, None, &enum_item.attrs);
+                    enum_item
+                        .variants
+                        .iter()
+                        .enumerate()
+                        .map(|(id, variant)| {
+                            let production =
+                                Production::new(format!("{}::{}", enum_item.ident, variant.ident))
+                                    .with_fields(field_names(&variant.fields))
+                                    .with_origin(enum_item.ident.to_string())
+                                    .with_location(
+                                        variant.ident.span().file(),
+                                        line(&variant.ident),
+                                    )
+                                    .with_docs(docs(&variant.attrs));
+                            grammar.set_production(key.clone().into(), Id(id), production);
+                            set_expected(grammar, &key, Some(Id(id)), &variant.attrs);
+
+                            (
+                                Id(id),
+                                fields_symbols(
+                                    &variant.fields,
+                                    productions,
+                                    grammar,
+                                    stack,
+                                    terminals,
+                                ),
+                            )
+                        })
+                        .collect()
+                }
                 Item::Struct(struct_item) => {
+                    set_expected(grammar, &key, None, &struct_item.attrs);
+                    let production = Production::new(struct_item.ident.to_string())
+                        .with_fields(field_names(&struct_item.fields))
+                        .with_origin(struct_item.ident.to_string())
+                        .with_location(struct_item.ident.span().file(), line(&struct_item.ident))
+                        .with_docs(docs(&struct_item.attrs));
+                    grammar.set_production(key.clone().into(), Id(0), production);
+
                     let mut rule = Rule::new();
                     rule.insert(
                         Id(0),
@@ -58,14 +87,7 @@ pub fn populate(
             }
         } else {
             match key.clone() {
-                TypeKey::Array(array) => collection_rule(
-                    symbol.clone(),
-                    *array.elem,
-                    productions,
-                    grammar,
-                    stack,
-                    terminals,
-                ),
+                TypeKey::Array(array) => array_rule(array, productions, grammar, stack, terminals),
                 TypeKey::Tuple(tuple) => tuple_rule(tuple, productions, grammar, stack, terminals),
                 TypeKey::Path(path) => path_rule(path, productions, grammar, stack, terminals),
             }
@@ -77,12 +99,20 @@ pub fn populate(
     symbol
 }
 
+/// Sets the label of `#[syntactical(expected = ...)]` in `attrs`, as the derive does.
+/// Malformed attributes are already reported by the derived impls
+fn set_expected(grammar: &mut Grammar, key: &TypeKey, id: Option<Id>, attrs: &[Attribute]) {
+    if let Some(label) = Attributes::parse(attrs).unwrap_or_default().expected {
+        grammar.set_expected(key.clone().into(), id, label.value());
+    }
+}
+
 fn fields_symbols(
     fields: &Fields,
     productions: &HashMap<TypeKey, Item>,
     grammar: &mut Grammar,
     stack: &mut Vec<TypeKey>,
-    terminals: &Vec<TypeKey>,
+    terminals: &mut Vec<TypeKey>,
 ) -> Vec<Symbol> {
     let mut symbols = Vec::new();
 
@@ -95,35 +125,77 @@ fn fields_symbols(
     symbols
 }
 
+/// Primitives, unknown identifiers and `()` are terminals, as in `builder`
+fn is_terminal(key: &TypeKey, productions: &HashMap<TypeKey, Item>) -> bool {
+    match key {
+        TypeKey::Path(path) => path.path.get_ident().is_some() && !productions.contains_key(key),
+        TypeKey::Tuple(tuple) => tuple.elems.is_empty(),
+        TypeKey::Array(_) => false,
+    }
+}
+
 fn collection_rule(
     collection: Symbol,
-    ty: Type,
+    elems: Vec<Type>,
     productions: &HashMap<TypeKey, Item>,
     grammar: &mut Grammar,
     stack: &mut Vec<TypeKey>,
-    terminals: &Vec<TypeKey>,
+    terminals: &mut Vec<TypeKey>,
 ) -> Rule {
-    let ty_key = ty.try_into().unwrap();
+    let mut symbols = elems
+        .into_iter()
+        .map(|ty| {
+            populate(
+                ty.try_into().unwrap(),
+                productions,
+                grammar,
+                stack,
+                terminals,
+            )
+        })
+        .collect::<Vec<_>>();
+    symbols.push(collection);
 
     let mut rule = Rule::new();
-    rule.insert(
-        Id(0),
-        vec![
-            populate(ty_key, productions, grammar, stack, terminals),
-            collection,
-        ],
-    );
+    rule.insert(Id(0), symbols);
     rule.insert(Id(1), vec![Symbol::Epsilon]);
 
     rule
 }
 
+fn array_rule(
+    array: TypeArray,
+    productions: &HashMap<TypeKey, Item>,
+    grammar: &mut Grammar,
+    stack: &mut Vec<TypeKey>,
+    terminals: &mut Vec<TypeKey>,
+) -> Rule {
+    let len = match &array.len {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(len), ..
+        }) => len.base10_parse::<usize>().unwrap(),
+        _ => panic!("Array lengths have to be integer literals"),
+    };
+    let child = populate(
+        (*array.elem).try_into().unwrap(),
+        productions,
+        grammar,
+        stack,
+        terminals,
+    );
+
+    let mut rule = Rule::new();
+    rule.insert(Id(0), vec![child; len]);
+
+    rule
+}
+
 fn tuple_rule(
     tuple: TypeTuple,
     productions: &HashMap<TypeKey, Item>,
     grammar: &mut Grammar,
     stack: &mut Vec<TypeKey>,
-    terminals: &Vec<TypeKey>,
+    terminals: &mut Vec<TypeKey>,
 ) -> Rule {
     let mut rule = Rule::new();
     rule.insert(
@@ -146,96 +218,76 @@ fn path_rule(
     productions: &HashMap<TypeKey, Item>,
     grammar: &mut Grammar,
     stack: &mut Vec<TypeKey>,
-    terminals: &Vec<TypeKey>,
+    terminals: &mut Vec<TypeKey>,
 ) -> Rule {
     let mut rule = Rule::new();
 
-    if let Some(ident) = type_path.path.get_ident() {
-        if PRIMITIVES.contains(&ident.to_string().as_str()) {
-            rule.insert(
-                Id(0),
-                vec![Symbol::terminal(TypeKey::new(ident.clone()).into())],
-            );
-        } else {
-            // warning treat ident as terminal
-            rule.insert(
-                Id(0),
-                vec![Symbol::terminal(TypeKey::new(ident.clone()).into())],
-            );
+    // maybe option, vec etc.
+    assert!(type_path.qself.is_none());
+    let path = &type_path.path;
+    assert!(path.leading_colon.is_none());
+    let segments = &path.segments;
+    assert_eq!(segments.len(), 1);
+    let PathSegment { ident, arguments } = &segments[0];
+
+    let arguments = match arguments {
+        PathArguments::AngleBracketed(args) => &args.args,
+        _ => panic!(),
+    };
+
+    let ident_str = ident.to_string();
+
+    if &ident_str == "Box" || &ident_str == "Option" {
+        assert_eq!(arguments.len(), 1);
+
+        let argument = &arguments[0];
+        let child_ty = match argument {
+            GenericArgument::Type(ty) => ty.clone(),
+            _ => panic!(),
+        };
+        let key_ty = child_ty.try_into().unwrap();
+        rule.insert(
+            Id(0),
+            vec![populate(key_ty, productions, grammar, stack, terminals)],
+        );
+        if &ident_str == "Option" {
+            rule.insert(Id(1), vec![Symbol::Epsilon]);
         }
-    } else {
-        // maybe option, vec etc.
-        assert!(type_path.qself.is_none());
-        let path = &type_path.path;
-        assert!(path.leading_colon.is_none());
-        let segments = &path.segments;
-        assert_eq!(segments.len(), 1);
-        let PathSegment { ident, arguments } = &segments[0];
-
-        let arguments = match arguments {
-            PathArguments::AngleBracketed(args) => &args.args,
+    } else if COLLECTIONS.contains(&ident_str.as_str()) {
+        assert_eq!(arguments.len(), 1);
+
+        let argument = &arguments[0];
+        let child_ty = match argument {
+            GenericArgument::Type(ty) => ty.clone(),
             _ => panic!(),
         };
 
-        let ident_str = ident.to_string();
+        let symbol = Symbol::nonterminal(TypeKey::Path(type_path).into());
+
+        return collection_rule(
+            symbol,
+            vec![child_ty],
+            productions,
+            grammar,
+            stack,
+            terminals,
+        );
+    } else if COLLECTION_MAPS.contains(&ident_str.as_str()) {
+        assert_eq!(arguments.len(), 2);
+
+        let elems = arguments
+            .iter()
+            .map(|arg| match arg {
+                GenericArgument::Type(ty) => ty.to_owned(),
+                _ => panic!(),
+            })
+            .collect();
 
-        if &ident_str == "Box" {
-            assert_eq!(arguments.len(), 1);
+        let symbol = Symbol::nonterminal(TypeKey::Path(type_path).into());
 
-            let argument = &arguments[0];
-            let child_ty = match argument {
-                GenericArgument::Type(ty) => ty.clone(),
-                _ => panic!(),
-            };
-            let key_ty = child_ty.try_into().unwrap();
-            rule.insert(
-                Id(0),
-                vec![populate(key_ty, productions, grammar, stack, terminals)],
-            );
-        } else if COLLECTIONS.contains(&ident_str.as_str()) {
-            assert_eq!(arguments.len(), 1);
-
-            let argument = &arguments[0];
-            let child_ty = match argument {
-                GenericArgument::Type(ty) => ty.clone(),
-                _ => panic!(),
-            };
-
-            let symbol = Symbol::nonterminal(TypeKey::Path(type_path).into());
-
-            return collection_rule(symbol, child_ty, productions, grammar, stack, terminals);
-        } else if COLLECTION_MAPS.contains(&ident_str.as_str()) {
-            assert_eq!(arguments.len(), 2);
-
-            let elems = arguments
-                .pairs()
-                .map(|pair| match pair {
-                    Pair::End(arg) => Pair::End(if let GenericArgument::Type(ty) = arg {
-                        ty.to_owned()
-                    } else {
-                        panic!()
-                    }),
-                    Pair::Punctuated(arg, p) => Pair::Punctuated(
-                        if let GenericArgument::Type(ty) = arg {
-                            ty.to_owned()
-                        } else {
-                            panic!()
-                        },
-                        p.to_owned(),
-                    ),
-                })
-                .collect();
-
-            let child_ty = Type::Tuple(TypeTuple {
-                paren_token: Paren::default(),
-                elems,
-            });
-            let symbol = Symbol::nonterminal(TypeKey::Path(type_path).into());
-
-            return collection_rule(symbol, child_ty, productions, grammar, stack, terminals);
-        } else {
-            panic!()
-        }
+        return collection_rule(symbol, elems, productions, grammar, stack, terminals);
+    } else {
+        panic!()
     }
 
     rule
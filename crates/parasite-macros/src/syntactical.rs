@@ -1,35 +1,118 @@
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
-use syn::{DeriveInput, Field, Ident, ItemImpl, Stmt, Variant};
+use quote::quote;
+use syn::{
+    Attribute, DeriveInput, Expr, Field, Fields, Ident, ItemImpl, Lit, LitStr, Meta, Variant,
+};
 
-pub fn terminal_impl(ident: Ident) -> ItemImpl {
-    syn::parse_quote!(
-        impl parasite::builder::Syntactical for #ident {}
-    )
+/// Options given with `#[syntactical(...)]`
+#[derive(Default)]
+pub struct Attributes {
+    pub name: Option<LitStr>,
+    pub display_name: Option<LitStr>,
+    pub expected: Option<LitStr>,
+}
+
+impl Attributes {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut attributes = Self::default();
+
+        for attr in attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("syntactical"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    attributes.name = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("display_name") {
+                    attributes.display_name = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("expected") {
+                    attributes.expected = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown syntactical attribute"))
+                }
+            })?;
+        }
+
+        Ok(attributes)
+    }
+
+    fn key_fn(&self) -> TokenStream {
+        match &self.name {
+            Some(name) => quote!(
+                fn key() -> parasite::grammar::Key {
+                    parasite::grammar::Key::of::<Self>().with_name(#name)
+                }
+            ),
+            None => TokenStream::new(),
+        }
+    }
+
+    fn display_name_fn(&self) -> TokenStream {
+        match &self.display_name {
+            Some(name) => quote!(
+                fn display_name() -> String {
+                    #name.to_string()
+                }
+            ),
+            None => TokenStream::new(),
+        }
+    }
+
+    fn expected_stmt(&self, id: TokenStream) -> TokenStream {
+        match &self.expected {
+            Some(label) => quote!(grammar.set_expected(key.clone(), #id, #label);),
+            None => TokenStream::new(),
+        }
+    }
+}
+
+pub fn terminal_impl(parsed: DeriveInput) -> syn::Result<ItemImpl> {
+    let ident = parsed.ident;
+    let attributes = Attributes::parse(&parsed.attrs)?;
+    let key = attributes.key_fn();
+    let display_name = attributes.display_name_fn();
+
+    Ok(syn::parse_quote!(
+        impl parasite::builder::Syntactical for #ident {
+            #key
+            #display_name
+        }
+    ))
 }
 
-pub fn syntactical_impl(parsed: DeriveInput) -> ItemImpl {
+pub fn syntactical_impl(parsed: DeriveInput) -> syn::Result<ItemImpl> {
     let ident = parsed.ident;
+    let attributes = Attributes::parse(&parsed.attrs)?;
+    let key = attributes.key_fn();
+    let display_name = attributes.display_name_fn();
+    let expected = attributes.expected_stmt(quote!(None));
 
     let rule_stmts = match parsed.data {
         syn::Data::Enum(data) => {
-            let stmts = enum_rule(Vec::from_iter(data.variants));
-            TokenStream::from_iter(stmts.map(ToTokens::into_token_stream))
+            let stmts = enum_rule(&ident, Vec::from_iter(data.variants))?;
+            TokenStream::from_iter(stmts)
         }
-        syn::Data::Struct(data) => struct_rule(Vec::from_iter(data.fields)).into_token_stream(),
+        syn::Data::Struct(data) => struct_rule(&ident, data.fields, docs(&parsed.attrs)),
         _ => unimplemented!(),
     };
 
-    syn::parse_quote!(
+    Ok(syn::parse_quote!(
         impl parasite::builder::Syntactical for #ident {
+            #key
+            #display_name
+
             fn generate(grammar: &mut parasite::grammar::Grammar, stack: &mut Vec<parasite::grammar::Key>) -> parasite::grammar::Symbol {
-                let key = parasite::grammar::Key::of::<Self>();
+                let key = Self::key();
 
                 if !Self::visited(grammar, stack) {
                     stack.push(key.clone());
 
                     let mut rule = parasite::grammar::Rule::new();
                     #rule_stmts
+                    #expected
 
                     grammar.insert(key.clone(), rule);
                 }
@@ -37,7 +120,7 @@ pub fn syntactical_impl(parsed: DeriveInput) -> ItemImpl {
                 parasite::grammar::Symbol::nonterminal(key)
             }
         }
-    )
+    ))
 }
 
 fn field_calls(field: Field) -> TokenStream {
@@ -45,15 +128,96 @@ fn field_calls(field: Field) -> TokenStream {
     quote!(<#ty as parasite::builder::Syntactical>::generate(grammar, stack))
 }
 
-pub fn struct_rule(fields: Vec<Field>) -> Stmt {
+/// The lines of the doc comments in `attrs`
+pub fn docs(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(doc) => Some(doc.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Names of the fields, `None` for tuple fields
+pub fn field_names(fields: &Fields) -> Vec<Option<String>> {
+    fields
+        .iter()
+        .map(|field| field.ident.as_ref().map(ToString::to_string))
+        .collect()
+}
+
+/// The line `ident` is written at
+pub fn line(ident: &Ident) -> u32 {
+    ident.span().start().line as u32
+}
+
+/// Records the production of `ident`, written at the item or variant `at`
+fn production_stmt(
+    ident: &Ident,
+    at: &Ident,
+    id: usize,
+    label: String,
+    fields: &Fields,
+    docs: Vec<String>,
+) -> TokenStream {
+    let fields = field_names(fields).into_iter().map(|field| match field {
+        Some(field) => quote!(Some(#field)),
+        None => quote!(None),
+    });
+    let line = line(at);
+
+    quote!(
+        grammar.set_production(
+            key.clone(),
+            parasite::grammar::Id(#id),
+            parasite::grammar::Production::new(#label)
+                .with_fields::<&str>([#(#fields ,)*])
+                .with_origin(concat!(module_path!(), "::", stringify!(#ident)))
+                .with_location(file!(), #line)
+                .with_docs::<&str>([#(#docs ,)*]),
+        );
+    )
+}
+
+pub fn struct_rule(ident: &Ident, fields: Fields, docs: Vec<String>) -> TokenStream {
+    let production = production_stmt(ident, ident, 0, ident.to_string(), &fields, docs);
     let calls = fields.into_iter().map(field_calls);
-    syn::parse_quote!(rule.insert(parasite::grammar::Id(0), vec![#(#calls ,)*]);)
+
+    quote!(
+        rule.insert(parasite::grammar::Id(0), vec![#(#calls ,)*]);
+        #production
+    )
 }
 
-pub fn enum_rule(variants: Vec<Variant>) -> impl Iterator<Item = Stmt> {
-    variants.into_iter().enumerate().map(|(id, variant)| {
-        let Variant { fields, .. } = variant;
-        let calls = fields.into_iter().map(field_calls);
-        syn::parse_quote!(rule.insert(parasite::grammar::Id(#id), vec![#(#calls ,)*]);)
-    })
+pub fn enum_rule(ident: &Ident, variants: Vec<Variant>) -> syn::Result<Vec<TokenStream>> {
+    variants
+        .into_iter()
+        .enumerate()
+        .map(|(id, variant)| {
+            let Variant {
+                attrs,
+                ident: variant,
+                fields,
+                ..
+            } = variant;
+            let expected =
+                Attributes::parse(&attrs)?.expected_stmt(quote!(Some(parasite::grammar::Id(#id))));
+            let label = format!("{ident}::{variant}");
+            let production = production_stmt(ident, &variant, id, label, &fields, docs(&attrs));
+            let calls = fields.into_iter().map(field_calls);
+
+            Ok(quote!(
+                rule.insert(parasite::grammar::Id(#id), vec![#(#calls ,)*]);
+                #expected
+                #production
+            ))
+        })
+        .collect()
 }
@@ -0,0 +1,188 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Attribute, DeriveInput, Field, Fields, ItemImpl, LitInt, LitStr};
+
+/// Options given with `#[unparse(...)]`, the layouts override the defaults of the combinators
+#[derive(Default)]
+struct Attributes {
+    /// The text of a terminal type
+    text: Option<LitStr>,
+    group: bool,
+    flat: bool,
+    nest: Option<LitInt>,
+    before: Option<TokenStream>,
+    after: Option<TokenStream>,
+}
+
+impl Attributes {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut attributes = Self::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("unparse")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("text") {
+                    attributes.text = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("group") {
+                    attributes.group = true;
+                } else if meta.path.is_ident("flat") {
+                    attributes.flat = true;
+                } else if meta.path.is_ident("nest") {
+                    attributes.nest = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("before") {
+                    attributes.before = Some(line(meta.value()?.parse()?)?);
+                } else if meta.path.is_ident("after") {
+                    attributes.after = Some(line(meta.value()?.parse()?)?);
+                } else {
+                    return Err(meta.error("unknown unparse attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(attributes)
+    }
+
+    /// Wraps `unparse` in the layout
+    fn layout(&self, unparse: TokenStream) -> TokenStream {
+        let Self {
+            group,
+            flat,
+            nest,
+            before,
+            after,
+            ..
+        } = self;
+
+        let mut layout = quote!(doc);
+        if *flat {
+            layout = quote!(#layout.flatten());
+        }
+        if let Some(nest) = nest {
+            layout = quote!(#layout.nest(#nest));
+        }
+        if *group {
+            layout = quote!(#layout.group());
+        }
+
+        match (*group || *flat || nest.is_some(), before, after) {
+            (false, None, None) => unparse,
+            _ => quote!(
+                unparser.layout(
+                    |doc| #layout,
+                    |unparser| {
+                        #before
+                        #unparse
+                        #after
+                    },
+                );
+            ),
+        }
+    }
+}
+
+/// The call emitting a line given as `"line"`, `"softline"` or `"hardline"`
+fn line(kind: LitStr) -> syn::Result<TokenStream> {
+    match kind.value().as_str() {
+        "line" => Ok(quote!(unparser.line();)),
+        "softline" => Ok(quote!(unparser.softline();)),
+        "hardline" => Ok(quote!(unparser.hardline();)),
+        _ => Err(syn::Error::new(
+            kind.span(),
+            "expected \"line\", \"softline\" or \"hardline\"",
+        )),
+    }
+}
+
+pub fn unparse_impl(parsed: DeriveInput) -> syn::Result<ItemImpl> {
+    let ident = parsed.ident;
+    let attributes = Attributes::parse(&parsed.attrs)?;
+
+    let body = match (&attributes.text, parsed.data) {
+        (Some(text), _) => quote!(unparser.terminal::<Self>(#text);),
+        (None, syn::Data::Struct(data)) => {
+            let (pattern, calls) = fields_pattern(&data.fields)?;
+            quote!(
+                let Self #pattern = self;
+                unparser.production::<Self>(parasite::grammar::Id(0));
+                #calls
+            )
+        }
+        (None, syn::Data::Enum(data)) => {
+            let arms = data
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(id, variant)| {
+                    let variant_ident = &variant.ident;
+                    let (pattern, calls) = fields_pattern(&variant.fields)?;
+                    let calls = Attributes::parse(&variant.attrs)?.layout(calls);
+                    Ok(quote!(
+                        Self::#variant_ident #pattern => {
+                            unparser.production::<Self>(parasite::grammar::Id(#id));
+                            #calls
+                        }
+                    ))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote!(
+                match self {
+                    #(#arms)*
+                }
+            )
+        }
+        (None, syn::Data::Union(_)) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "unions can not be unparsed",
+            ))
+        }
+    };
+    let body = attributes.layout(body);
+
+    Ok(syn::parse_quote!(
+        impl parasite::unparse::Unparse for #ident {
+            fn unparse(&self, unparser: &mut parasite::unparse::Unparser) {
+                #body
+            }
+        }
+    ))
+}
+
+/// A pattern binding the fields in order and the calls unparsing them
+fn fields_pattern(fields: &Fields) -> syn::Result<(TokenStream, TokenStream)> {
+    let bindings = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("field_{index}"),
+        })
+        .collect::<Vec<_>>();
+
+    let pattern = match fields {
+        Fields::Named(_) => quote!({ #(#bindings ,)* }),
+        Fields::Unnamed(_) => quote!(( #(#bindings ,)* )),
+        Fields::Unit => TokenStream::new(),
+    };
+    let calls = fields
+        .iter()
+        .zip(&bindings)
+        .map(|(field, binding)| field_call(field, quote!(#binding)))
+        .collect::<syn::Result<TokenStream>>()?;
+
+    Ok((pattern, calls))
+}
+
+fn field_call(field: &Field, binding: TokenStream) -> syn::Result<TokenStream> {
+    let attributes = Attributes::parse(&field.attrs)?;
+    if let Some(text) = &attributes.text {
+        return Err(syn::Error::new(
+            text.span(),
+            "text is only allowed on terminal types",
+        ));
+    }
+
+    Ok(attributes.layout(quote!(
+        parasite::unparse::Unparse::unparse(#binding, unparser);
+    )))
+}
@@ -0,0 +1,115 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, Ident, ItemImpl};
+
+/// The traversals which can be derived, they differ in how the fields are taken
+#[derive(Clone, Copy)]
+pub enum Traversal {
+    Visit,
+    VisitMut,
+    Fold,
+}
+
+pub fn traversal_impl(parsed: DeriveInput, traversal: Traversal) -> syn::Result<ItemImpl> {
+    let ident = parsed.ident;
+
+    // one match arm per variant, a struct is its only variant
+    let arms = match parsed.data {
+        Data::Struct(data) => vec![arm(quote!(Self), &data.fields, traversal)],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let variant_ident = &variant.ident;
+                arm(quote!(Self::#variant_ident), &variant.fields, traversal)
+            })
+            .collect(),
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "unions can not be traversed",
+            ));
+        }
+    };
+
+    Ok(match traversal {
+        Traversal::Visit => syn::parse_quote!(
+            impl parasite::visit::Visit for #ident {
+                fn visit<V: parasite::visit::Visitor + ?Sized>(&self, visitor: &mut V) {
+                    visitor.enter(self);
+                    match self {
+                        #(#arms)*
+                    }
+                    visitor.exit(self);
+                }
+            }
+        ),
+        Traversal::VisitMut => syn::parse_quote!(
+            impl parasite::visit::VisitMut for #ident {
+                fn visit_mut<V: parasite::visit::VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+                    visitor.enter(self);
+                    match self {
+                        #(#arms)*
+                    }
+                    visitor.exit(self);
+                }
+            }
+        ),
+        Traversal::Fold => syn::parse_quote!(
+            impl parasite::visit::Fold for #ident {
+                fn fold_children<F: parasite::visit::Folder + ?Sized>(self, folder: &mut F) -> Self {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        ),
+    })
+}
+
+/// Binds the fields of `path` and traverses them in order
+fn arm(path: TokenStream, fields: &Fields, traversal: Traversal) -> TokenStream {
+    let bindings = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("field_{index}"),
+        })
+        .collect::<Vec<_>>();
+    let bound = match fields {
+        Fields::Named(_) => quote!({ #(#bindings,)* }),
+        fields => pattern(fields, &bindings, |binding| quote!(#binding)),
+    };
+
+    match traversal {
+        Traversal::Visit => quote!(
+            #path #bound => {
+                #(parasite::visit::Visit::visit(#bindings, visitor);)*
+            }
+        ),
+        Traversal::VisitMut => quote!(
+            #path #bound => {
+                #(parasite::visit::VisitMut::visit_mut(#bindings, visitor);)*
+            }
+        ),
+        Traversal::Fold => {
+            let folded = pattern(fields, &bindings, |binding| quote!(folder.fold(#binding)));
+            quote!(#path #bound => #path #folded,)
+        }
+    }
+}
+
+/// The fields in the shape of `fields`, with the value of each binding given by `value`
+fn pattern(
+    fields: &Fields,
+    bindings: &[Ident],
+    value: impl Fn(&Ident) -> TokenStream,
+) -> TokenStream {
+    let values = bindings.iter().map(value);
+    match fields {
+        Fields::Named(_) => quote!({ #(#bindings: #values,)* }),
+        Fields::Unnamed(_) => quote!(( #(#values,)* )),
+        Fields::Unit => TokenStream::new(),
+    }
+}
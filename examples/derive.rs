@@ -6,9 +6,11 @@ use parasite_core::{
 use parasite_macros::*;
 
 #[derive(Terminal)]
+#[syntactical(display_name = "leaf")]
 pub struct Leaf {}
 
 #[derive(Syntactical)]
+#[syntactical(expected = "branch")]
 pub enum Branch {
     Branch(Rec<(Leaf, Branch)>),
     Leaf(Leaf),
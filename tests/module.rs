@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use parasite::{
+    builder::Syntactical,
+    grammar::{Grammar, Id, Key},
+    module,
+};
+
+module!(
+    mod ast {
+        #[begin]
+        pub enum S {
+            A((u8, A, u8)),
+        }
+
+        #[syntactical(expected = "a group")]
+        pub enum A {
+            /// A nested group
+            S((bool, Box<S>, Option<u8>, bool)),
+            End(Pair),
+        }
+
+        /// Numbers and their end, derived by hand, which `module!` leaves alone
+        #[derive(parasite::Syntactical)]
+        #[syntactical(name = "pair")]
+        pub struct Pair {
+            first: Vec<u8>,
+            second: Number,
+        }
+
+        #[terminal]
+        #[syntactical(display_name = "number")]
+        pub struct Number;
+    }
+);
+
+mod derived {
+    use parasite::{Syntactical, Terminal};
+
+    #[derive(Syntactical)]
+    pub enum S {
+        A((u8, A, u8)),
+    }
+
+    #[derive(Syntactical)]
+    #[syntactical(expected = "a group")]
+    pub enum A {
+        /// A nested group
+        S((bool, Box<S>, Option<u8>, bool)),
+        End(Pair),
+    }
+
+    /// Numbers and their end
+    #[derive(Syntactical)]
+    #[syntactical(name = "pair")]
+    pub struct Pair {
+        first: Vec<u8>,
+        second: Number,
+    }
+
+    #[derive(Terminal)]
+    #[syntactical(display_name = "number")]
+    pub struct Number;
+}
+
+fn generate<T: Syntactical>() -> Grammar {
+    let mut grammar = Grammar::new(Key::of::<T>());
+    let mut stack = Vec::new();
+
+    T::generate(&mut grammar, &mut stack);
+
+    grammar
+}
+
+/// The productions by the names of their keys, as types of different modules never share keys
+fn shape(grammar: &Grammar) -> BTreeMap<String, BTreeMap<usize, Vec<String>>> {
+    grammar
+        .productions
+        .iter()
+        .map(|(key, rule)| {
+            let rule = rule
+                .iter()
+                .map(|(id, symbols)| (id.0, symbols.iter().map(ToString::to_string).collect()))
+                .collect();
+            (key.to_string(), rule)
+        })
+        .collect()
+}
+
+/// The grammar `module!` populates at compile time equals the one of the derives
+#[test]
+fn module_1() {
+    assert_eq!(ast::grammar(), generate::<ast::S>());
+}
+
+#[test]
+fn module_2() {
+    let module = ast::grammar();
+    let derived = generate::<derived::S>();
+
+    assert_eq!(shape(&module), shape(&derived));
+    let names = |grammar: &Grammar| {
+        let names = grammar.names.iter();
+        names
+            .map(|(key, name)| (key.to_string(), name.clone()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    assert_eq!(names(&module), names(&derived));
+    assert_eq!(module.start.to_string(), derived.start.to_string());
+}
+
+/// A renamed type is shown by its name and keeps the identity of its type
+#[test]
+fn module_3() {
+    let key = Key::of::<derived::Pair>();
+    assert_eq!(derived::Pair::key(), key);
+    assert_eq!(derived::Pair::key().to_string(), "pair");
+
+    let grammars = [
+        (ast::grammar(), Key::of::<ast::Pair>()),
+        (generate::<derived::S>(), key),
+    ];
+    for (grammar, key) in grammars {
+        let (renamed, _) = grammar.productions.get_key_value(&key).unwrap();
+        assert_eq!(renamed.to_string(), "pair");
+    }
+}
+
+/// Productions know the file and line of the item or variant they are written at
+#[test]
+fn module_4() {
+    let location = |grammar: &Grammar, key: Key, id: usize| {
+        let production = grammar.production(&key, Id(id)).unwrap();
+        production.location.clone().unwrap()
+    };
+    let at = |line: u32| (file!().to_string(), line);
+
+    let module = ast::grammar();
+    assert_eq!(location(&module, Key::of::<ast::S>(), 0), at(13));
+    assert_eq!(location(&module, Key::of::<ast::A>(), 1), at(20));
+    assert_eq!(location(&module, Key::of::<ast::Pair>(), 0), at(26));
+
+    let derived = generate::<derived::S>();
+    assert_eq!(location(&derived, Key::of::<derived::A>(), 1), at(50));
+    assert_eq!(location(&derived, Key::of::<derived::Pair>(), 0), at(56));
+    assert_eq!(
+        derived
+            .production(&Key::of::<derived::Pair>(), Id(0))
+            .unwrap()
+            .origin,
+        Some("module::derived::Pair".to_string())
+    );
+}
@@ -0,0 +1,142 @@
+use parasite::{
+    builder::Syntactical,
+    combinators::{DelimitedBy, Identifier, Just, Rec, SeparatedBy},
+    earley::Policy,
+    grammar::{Grammar, Key, Terminal},
+    span::Token,
+    unparse::Unparse,
+    Syntactical, Terminal, Unparse,
+};
+
+#[derive(Terminal, Unparse)]
+#[unparse(text = "leaf")]
+pub struct Leaf {}
+
+#[derive(Syntactical, Unparse)]
+pub enum Tree {
+    Node(DelimitedBy<Just<'['>, Just<']'>, SeparatedBy<Just<','>, Tree>>),
+    Labeled { label: char, tree: Rec<Tree> },
+    Leaf(Leaf),
+}
+
+#[derive(Syntactical, Unparse)]
+pub struct Forest(Vec<Tree>, Option<u8>);
+
+/// A named tree, kept on one line
+#[derive(Syntactical, Unparse)]
+#[unparse(group)]
+pub struct Item {
+    #[unparse(after = "line")]
+    name: Identifier,
+    #[unparse(flat)]
+    tree: Tree,
+    #[unparse(before = "hardline")]
+    end: Just<';'>,
+}
+
+fn generate<T: Syntactical>() -> Grammar {
+    let mut grammar = Grammar::new(Key::of::<T>());
+    let mut stack = Vec::new();
+
+    T::generate(&mut grammar, &mut stack);
+    grammar
+}
+
+fn node(trees: Vec<Tree>) -> Tree {
+    Tree::Node(DelimitedBy::new(SeparatedBy::new(trees)))
+}
+
+/// Splits the text of a `Forest` into its terminals: words are leaves, labels or numbers
+fn lex(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        let key = match c {
+            ' ' => continue,
+            '[' => Key::of::<Just<'['>>(),
+            ']' => Key::of::<Just<']'>>(),
+            ',' => Key::of::<Just<','>>(),
+            c if c.is_alphanumeric() => {
+                while let Some((next, c)) = chars.next_if(|(_, c)| c.is_alphanumeric()) {
+                    end = next + c.len_utf8();
+                }
+                match &text[start..end] {
+                    "leaf" => Key::of::<Leaf>(),
+                    word if word.chars().all(|c| c.is_ascii_digit()) => Key::of::<u8>(),
+                    _ => Key::of::<char>(),
+                }
+            }
+            c => panic!("{c} is no terminal of a forest"),
+        };
+        tokens.push(Token::new(Terminal::from(key), start..end));
+    }
+
+    tokens
+}
+
+#[test]
+fn unparse_round_trip() {
+    let forest = Forest(
+        vec![
+            Tree::Leaf(Leaf {}),
+            node(vec![
+                Tree::Labeled {
+                    label: 'a',
+                    tree: Rec(Box::new(Tree::Leaf(Leaf {}))),
+                },
+                node(vec![Tree::Leaf(Leaf {})]),
+            ]),
+        ],
+        Some(7),
+    );
+
+    let unparser = forest.to_unparser();
+    let text = unparser.text();
+    assert_eq!(text, "leaf[a leaf,[leaf]]7");
+
+    // the label is kept apart from the leaf, so the text lexes into the same tokens
+    let tokens = lex(&text);
+    assert_eq!(tokens, unparser.tokens());
+    let terminals = tokens
+        .iter()
+        .map(|token| token.terminal.clone())
+        .collect::<Vec<_>>();
+
+    // `SeparatedBy` of whole trees is not LL(k), the Earley parser takes any grammar
+    let grammar = generate::<Forest>();
+    let forest = grammar.forest(&terminals).unwrap();
+    assert!(!forest.is_ambiguous());
+
+    let applied = forest.tree(Policy::Left);
+    assert_eq!(applied, unparser.applied());
+    let tree = grammar.tree(&applied, &tokens);
+    assert_eq!(tree.span.range(), 0..text.len());
+}
+
+#[test]
+fn unparse_pretty() {
+    let forest = Forest(
+        vec![
+            Tree::Leaf(Leaf {}),
+            node(vec![
+                Tree::Labeled {
+                    label: 'a',
+                    tree: Rec(Box::new(Tree::Leaf(Leaf {}))),
+                },
+                node(vec![Tree::Leaf(Leaf {})]),
+            ]),
+        ],
+        None,
+    );
+    assert_eq!(forest.pretty(80), "leaf[aleaf, [leaf]]");
+    assert_eq!(forest.pretty(10), "leaf[\n    aleaf,\n    [leaf]\n]");
+
+    let item = Item {
+        name: Identifier("item".to_string()),
+        tree: node(vec![Tree::Leaf(Leaf {}), Tree::Leaf(Leaf {})]),
+        end: Just(),
+    };
+    assert_eq!(item.pretty(10), "item\n[leaf, leaf]\n;");
+}
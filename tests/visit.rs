@@ -0,0 +1,117 @@
+use std::any::Any;
+
+use parasite::{
+    combinators::{
+        DelimitedBy, Identifier, Just, NonEmptyVec, PaddedBy, Rec, SeparatedBy, WhiteSpace,
+    },
+    visit::{fold_as, Fold, Folder, Visit, VisitMut, Visitor, VisitorMut},
+    Fold, Syntactical, Visit, VisitMut,
+};
+
+#[derive(Debug, PartialEq, Syntactical, Visit, VisitMut, Fold)]
+pub enum Expr {
+    Number(u32),
+    Variable(Identifier),
+    Call {
+        name: Identifier,
+        args: DelimitedBy<Just<'('>, Just<')'>, SeparatedBy<Just<','>, Expr>>,
+    },
+    Block(NonEmptyVec<PaddedBy<WhiteSpace, Rec<Expr>>>),
+    Nil,
+}
+
+#[derive(Debug, PartialEq, Syntactical, Visit, VisitMut, Fold)]
+pub struct Program {
+    body: Vec<Expr>,
+    result: Option<Box<Expr>>,
+}
+
+fn variable(name: &str) -> Expr {
+    Expr::Variable(Identifier(name.to_string()))
+}
+
+fn call(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::Call {
+        name: Identifier(name.to_string()),
+        args: DelimitedBy::new(SeparatedBy::new(args)),
+    }
+}
+
+fn block(exprs: Vec<Expr>) -> Expr {
+    Expr::Block(NonEmptyVec(
+        exprs
+            .into_iter()
+            .map(|expr| PaddedBy::new(Rec(Box::new(expr))))
+            .collect(),
+    ))
+}
+
+fn program() -> Program {
+    Program {
+        body: vec![
+            call("f", vec![Expr::Number(1), variable("x")]),
+            block(vec![Expr::Nil, Expr::Number(2)]),
+        ],
+        result: Some(Box::new(variable("x"))),
+    }
+}
+
+/// The numbers in the order they are entered
+#[derive(Default)]
+struct Numbers(Vec<u32>);
+
+impl Visitor for Numbers {
+    fn enter<T: Visit>(&mut self, node: &T) {
+        if let Some(Expr::Number(number)) = (node as &dyn Any).downcast_ref() {
+            self.0.push(*number);
+        }
+    }
+}
+
+struct Double;
+
+impl VisitorMut for Double {
+    fn enter<T: VisitMut>(&mut self, node: &mut T) {
+        if let Some(number) = (node as &mut dyn Any).downcast_mut::<u32>() {
+            *number *= 2;
+        }
+    }
+}
+
+/// Replaces the variable `x` by a number
+struct Substitute(u32);
+
+impl Folder for Substitute {
+    fn fold<T: Fold>(&mut self, node: T) -> T {
+        fold_as(node.fold_children(self), |expr| match expr {
+            Expr::Variable(Identifier(name)) if name == "x" => Expr::Number(self.0),
+            expr => expr,
+        })
+    }
+}
+
+#[test]
+fn visit_derived() {
+    let mut program = program();
+
+    let mut numbers = Numbers::default();
+    program.visit(&mut numbers);
+    assert_eq!(numbers.0, [1, 2]);
+
+    program.visit_mut(&mut Double);
+    let mut numbers = Numbers::default();
+    program.visit(&mut numbers);
+    assert_eq!(numbers.0, [2, 4]);
+
+    let program = Substitute(7).fold(program);
+    assert_eq!(
+        program,
+        Program {
+            body: vec![
+                call("f", vec![Expr::Number(2), Expr::Number(7)]),
+                block(vec![Expr::Nil, Expr::Number(4)]),
+            ],
+            result: Some(Box::new(Expr::Number(7))),
+        }
+    );
+}